@@ -3,7 +3,7 @@ use crate::core::math::Rect;
 use crate::scene2d::light::Light;
 use crate::{
     core::{
-        algebra::{Matrix4, Vector2},
+        algebra::{Matrix4, Point3, Vector2},
         pool::Handle,
     },
     physics::parry::utils::hashmap::Entry,
@@ -111,7 +111,6 @@ pub struct Renderer2d {
 #[derive(Default)]
 struct BatchStorage {
     batches: Vec<Batch>,
-    index_map: HashMap<u64, usize>,
 }
 
 impl BatchStorage {
@@ -122,14 +121,17 @@ impl BatchStorage {
         texture_cache: &mut TextureCache,
         white_dummy: Rc<RefCell<GpuTexture>>,
     ) {
-        self.index_map.clear();
         for batch in self.batches.iter_mut() {
             batch.instances.clear();
         }
 
-        let mut batch_index = 0;
-        for node in scene.graph.linear_iter() {
-            if let Node::Sprite(sprite) = node {
+        // Sprites are visited in draw order of the graph and only consecutive sprites with the
+        // same texture, space and clip rectangle are batched together, so batches drawn one
+        // after another keep the draw order.
+        let mut batch_count = 0;
+        let graph = &scene.graph;
+        for handle in graph.render_order(graph.get_root()) {
+            if let Node::Sprite(sprite) = &graph[handle] {
                 if !sprite.global_visibility() {
                     continue;
                 }
@@ -143,33 +145,38 @@ impl BatchStorage {
                     },
                 );
 
-                let texture_id = &*texture.borrow() as *const _ as u64;
-                let index = *self.index_map.entry(texture_id).or_insert_with(|| {
-                    let index = batch_index;
-                    batch_index += 1;
-                    index as usize
-                });
-
-                // Reuse old batches to prevent redundant memory allocations
-                let batch = if let Some(batch) = self.batches.get_mut(index) {
-                    batch.texture = texture.clone();
-                    batch
-                } else {
-                    self.batches.push(Batch {
-                        instances: Default::default(),
-                        texture: texture.clone(),
-                    });
-                    self.batches.last_mut().unwrap()
-                };
-
-                batch.instances.push(Instance {
+                let instance = Instance {
                     gpu_data: InstanceData {
                         color: sprite.color(),
                         world_matrix: sprite.global_transform()
                             * Matrix4::new_scaling(sprite.size()),
                     },
                     bounds: sprite.global_bounds(),
-                });
+                    screen_space: graph.is_in_screen_space(handle),
+                    clip: graph.clip_rect_of(handle),
+                };
+
+                let continues_last = batch_count > 0 && {
+                    let last = &self.batches[batch_count - 1];
+                    let first = &last.instances[0];
+                    Rc::ptr_eq(&last.texture, &texture)
+                        && first.screen_space == instance.screen_space
+                        && first.clip == instance.clip
+                };
+                if !continues_last {
+                    // Reuse old batches to prevent redundant memory allocations
+                    if let Some(batch) = self.batches.get_mut(batch_count) {
+                        batch.texture = texture.clone();
+                    } else {
+                        self.batches.push(Batch {
+                            instances: Default::default(),
+                            texture: texture.clone(),
+                        });
+                    }
+                    batch_count += 1;
+                }
+
+                self.batches[batch_count - 1].instances.push(instance);
             }
         }
     }
@@ -178,6 +185,43 @@ impl BatchStorage {
 struct Instance {
     gpu_data: InstanceData,
     bounds: Rect<f32>,
+    // Screen-space instances are drawn without view matrix of a camera.
+    screen_space: bool,
+    // World-space rectangle that the instance is clipped by.
+    clip: Option<Rect<f32>>,
+}
+
+/// Projects world-space clip rectangle to the viewport and returns scissor box (x, y, w, h)
+/// in OpenGL window coordinates.
+fn scissor_box(
+    clip: Rect<f32>,
+    view_projection: &Matrix4<f32>,
+    viewport: Rect<i32>,
+) -> (i32, i32, i32, i32) {
+    let (min, max) = [
+        clip.left_top_corner(),
+        clip.right_top_corner(),
+        clip.right_bottom_corner(),
+        clip.left_bottom_corner(),
+    ]
+    .iter()
+    .map(|p| {
+        let ndc = view_projection.transform_point(&Point3::new(p.x, p.y, 0.0));
+        Vector2::new(
+            viewport.x() as f32 + (ndc.x + 1.0) * 0.5 * viewport.w() as f32,
+            viewport.y() as f32 + (ndc.y + 1.0) * 0.5 * viewport.h() as f32,
+        )
+    })
+    .fold(
+        (Vector2::repeat(f32::MAX), Vector2::repeat(-f32::MAX)),
+        |(min, max), p| (min.inf(&p), max.sup(&p)),
+    );
+    (
+        min.x.floor() as i32,
+        min.y.floor() as i32,
+        (max.x - min.x).ceil() as i32,
+        (max.y - min.y).ceil() as i32,
+    )
 }
 
 struct Batch {
@@ -315,67 +359,105 @@ impl Renderer2d {
                     }
                 }
 
+                // Screen-space sprites are not affected by view of the camera.
+                let passes = [(false, view_projection), (true, camera.projection_matrix())];
+
+                // Clip rectangles are in world coordinates.
+                let world_view_projection = view_projection;
+
                 for batch in self.batch_storage.batches.iter() {
-                    self.instance_data_set.clear();
+                    // Instances with different clip rectangles are drawn separately, each with
+                    // its own scissor box.
+                    let mut clips = Vec::new();
                     for instance in batch.instances.iter() {
-                        if viewport_f32.intersects(instance.bounds) {
-                            self.instance_data_set.push(instance.gpu_data.clone());
+                        if !clips.contains(&instance.clip) {
+                            clips.push(instance.clip);
                         }
                     }
 
-                    quad.set_buffer_data(state, 1, &self.instance_data_set);
-
-                    stats += frame_buffer.draw_instances(
-                        batch.instances.len(),
-                        quad,
-                        state,
-                        viewport,
-                        &self.sprite_shader.program,
-                        &DrawParameters {
-                            cull_face: CullFace::Back,
-                            culling: false,
-                            color_write: Default::default(),
-                            depth_write: false,
-                            stencil_test: false,
-                            depth_test: false,
-                            blend: true,
-                        },
-                        &[
-                            (
-                                self.sprite_shader.wvp_matrix.clone(),
-                                UniformValue::Matrix4(&view_projection),
-                            ),
-                            (
-                                self.sprite_shader.diffuse_texture.clone(),
-                                UniformValue::Sampler {
-                                    index: 0,
-                                    texture: batch.texture.clone(),
+                    for (screen_space, view_projection) in passes.iter() {
+                        for clip in clips.iter() {
+                            self.instance_data_set.clear();
+                            for instance in batch.instances.iter() {
+                                if instance.screen_space == *screen_space
+                                    && instance.clip == *clip
+                                    && viewport_f32.intersects(instance.bounds)
+                                {
+                                    self.instance_data_set.push(instance.gpu_data.clone());
+                                }
+                            }
+
+                            if self.instance_data_set.is_empty() {
+                                continue;
+                            }
+
+                            match clip {
+                                Some(clip) => {
+                                    let (x, y, w, h) =
+                                        scissor_box(*clip, &world_view_projection, viewport);
+                                    state.set_scissor_test(true);
+                                    state.set_scissor_box(x, y, w, h);
+                                }
+                                None => state.set_scissor_test(false),
+                            }
+
+                            quad.set_buffer_data(state, 1, &self.instance_data_set);
+
+                            stats += frame_buffer.draw_instances(
+                                self.instance_data_set.len(),
+                                quad,
+                                state,
+                                viewport,
+                                &self.sprite_shader.program,
+                                &DrawParameters {
+                                    cull_face: CullFace::Back,
+                                    culling: false,
+                                    color_write: Default::default(),
+                                    depth_write: false,
+                                    stencil_test: false,
+                                    depth_test: false,
+                                    blend: true,
                                 },
-                            ),
-                            (
-                                self.sprite_shader.light_count.clone(),
-                                UniformValue::Integer(light_count as i32),
-                            ),
-                            (
-                                self.sprite_shader.light_color_radius.clone(),
-                                UniformValue::Vector4Array(&light_color_radius),
-                            ),
-                            (
-                                self.sprite_shader.light_position_direction.clone(),
-                                UniformValue::Vector4Array(&light_position_direction),
-                            ),
-                            (
-                                self.sprite_shader.light_parameters.clone(),
-                                UniformValue::Vector2Array(&light_parameters),
-                            ),
-                            (
-                                self.sprite_shader.ambient_light_color.clone(),
-                                UniformValue::Vector3(&scene.ambient_light_color.as_frgb()),
-                            ),
-                        ],
-                    );
+                                &[
+                                    (
+                                        self.sprite_shader.wvp_matrix.clone(),
+                                        UniformValue::Matrix4(view_projection),
+                                    ),
+                                    (
+                                        self.sprite_shader.diffuse_texture.clone(),
+                                        UniformValue::Sampler {
+                                            index: 0,
+                                            texture: batch.texture.clone(),
+                                        },
+                                    ),
+                                    (
+                                        self.sprite_shader.light_count.clone(),
+                                        UniformValue::Integer(light_count as i32),
+                                    ),
+                                    (
+                                        self.sprite_shader.light_color_radius.clone(),
+                                        UniformValue::Vector4Array(&light_color_radius),
+                                    ),
+                                    (
+                                        self.sprite_shader.light_position_direction.clone(),
+                                        UniformValue::Vector4Array(&light_position_direction),
+                                    ),
+                                    (
+                                        self.sprite_shader.light_parameters.clone(),
+                                        UniformValue::Vector2Array(&light_parameters),
+                                    ),
+                                    (
+                                        self.sprite_shader.ambient_light_color.clone(),
+                                        UniformValue::Vector3(&scene.ambient_light_color.as_frgb()),
+                                    ),
+                                ],
+                            );
+                        }
+                    }
                 }
             }
+
+            state.set_scissor_test(false);
         }
         Ok(stats)
     }
@@ -2,23 +2,287 @@ use crate::scene::base::PhysicsBinding;
 use crate::{
     core::{
         algebra::{Matrix4, Vector2},
+        color::Color,
+        math::Rect,
         pool::Handle,
+        uuid::Uuid,
         visitor::prelude::*,
     },
-    scene2d::{graph::Graph, node::Node, transform::Transform},
+    scene2d::{graph::Graph, node::Node, transform::Transform, visit_optional},
 };
-use std::cell::Cell;
+use std::{
+    any::Any,
+    cell::Cell,
+    ops::{BitOr, BitOrAssign},
+};
+
+/// A set of per-node behavior toggles packed into a single integer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NodeFlags(u32);
+
+impl NodeFlags {
+    /// Node should be drawn. Global visibility of a node is a combination of this flag
+    /// of the node itself and flags of all its ancestors.
+    pub const VISIBLE: Self = Self(1);
+
+    /// Node takes part in per-frame logic updates (cameras, etc). Disabled node disables
+    /// whole its subtree.
+    pub const ENABLED: Self = Self(1 << 1);
+
+    /// Node casts shadows from light sources.
+    pub const CAST_SHADOW: Self = Self(1 << 2);
+
+    /// Global transform of the node won't be recalculated, the node will keep its last
+    /// calculated global transform until the flag is removed. Descendants are still updated
+    /// relative to it.
+    pub const FROZEN: Self = Self(1 << 3);
+
+    /// Transform of the node (and its whole subtree) is relative to the screen instead of the
+    /// world, so the node stays fixed on screen when a camera moves. Useful for HUD.
+    pub const SCREEN_SPACE: Self = Self(1 << 4);
+
+    /// Descendants of the node are clipped by its world bounds, see [`Graph::clip_rect_of`].
+    pub const CLIP: Self = Self(1 << 5);
+
+    /// Node is ignored by [`Graph::pick`], while still being drawn. The flag is inverted (set
+    /// means "not pickable") so nodes saved before the flag existed stay pickable.
+    pub const NOT_PICKABLE: Self = Self(1 << 6);
+
+    /// Subtree of the node is immovable and can be merged in a single batch by a renderer,
+    /// see [`Graph::set_static`].
+    pub const STATIC: Self = Self(1 << 7);
+
+    /// Returns a set without any flags.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns raw bits of the set.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Creates a set from raw bits.
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    /// Returns true if all flags of `other` are set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Sets all flags of `other`.
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    /// Clears all flags of `other`.
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+
+    /// Sets or clears all flags of `other` depending on `value`.
+    pub fn set(&mut self, other: Self, value: bool) {
+        if value {
+            self.insert(other);
+        } else {
+            self.remove(other);
+        }
+    }
+}
+
+impl Default for NodeFlags {
+    fn default() -> Self {
+        Self::VISIBLE | Self::ENABLED | Self::CAST_SHADOW
+    }
+}
+
+impl BitOr for NodeFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for NodeFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl Visit for NodeFlags {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        self.0.visit(name, visitor)
+    }
+}
+
+/// Defines how a node arranges its children, see [`Graph::perform_layout`].
+#[derive(Copy, Clone, Debug, PartialEq, Visit)]
+pub enum Layout {
+    /// Children keep positions that were set manually.
+    None,
+    /// Children are placed in a row along X axis, bounds of neighbour children are separated
+    /// by `spacing`.
+    Horizontal { spacing: f32 },
+    /// Children are placed in a column along Y axis, bounds of neighbour children are
+    /// separated by `spacing`.
+    Vertical { spacing: f32 },
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+/// Minimal movement of a node that is reported as a change, see
+/// [`Graph::set_change_threshold`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Visit)]
+pub struct ChangeThreshold {
+    /// Minimal distance (in world units) between global positions.
+    pub position: f32,
+    /// Minimal difference (in radians) between global rotations.
+    pub rotation: f32,
+}
+
+/// A property of a node of prefab instance together with its value, see
+/// [`Graph::set_prefab_override`].
+#[derive(Clone, Debug, PartialEq, Visit)]
+pub enum PrefabProperty {
+    Name(String),
+    Visibility(bool),
+    Position(Vector2<f32>),
+    Rotation(f32),
+    Scale(Vector2<f32>),
+    /// Color of a sprite or a light, ignored for other kinds of nodes.
+    Color(Color),
+}
+
+impl Default for PrefabProperty {
+    fn default() -> Self {
+        Self::Visibility(true)
+    }
+}
+
+/// Instance-specific value of a property of a node of prefab instance, which survives
+/// [`Graph::reapply_prefab`].
+#[derive(Clone, Debug, Default, PartialEq, Visit)]
+pub struct PrefabOverride {
+    /// Stable id of a node of the instance.
+    pub node: Uuid,
+    pub property: PrefabProperty,
+}
+
+/// Shape of a [gizmo](Gizmo), in local coordinates of a node.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GizmoShape {
+    /// A cross at the origin of a node, arms have given length.
+    Cross { size: f32 },
+    /// Local bounds of a node, or nothing if the node has no bounds.
+    Bounds,
+    /// A circle around the origin of a node.
+    Circle { radius: f32 },
+}
+
+/// Debug annotation of a node that editors and debug overlays can draw, see
+/// [`Graph::set_gizmo`]. Runtime-only, never saved and never copied.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Gizmo {
+    pub shape: GizmoShape,
+    pub color: Color,
+}
+
+/// Closure that is called for a node on every [`Graph::update`] with the node itself and time
+/// step (in seconds), see [`Graph::set_update_callback`].
+pub type UpdateCallback = Box<dyn FnMut(&mut Node, f32) + Send>;
+
+/// Closure that is called with new global transform of a node when it changes, see
+/// [`Graph::watch_transform`].
+pub type TransformWatcher = Box<dyn FnMut(&Matrix4<f32>) + Send>;
+
+/// Makes a node copy world rotation of another node, see [`Graph::set_rotation_constraint`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Visit)]
+pub struct RotationConstraint {
+    /// A node which world rotation is copied.
+    pub target: Handle<Node>,
+    /// Angle (in radians) that is added to world rotation of the target.
+    pub offset: f32,
+}
 
-#[derive(Visit)]
 pub struct Base {
     transform: Transform,
     pub(in crate) global_transform: Cell<Matrix4<f32>>,
-    pub(in crate) visibility: bool,
+    pub(in crate) flags: NodeFlags,
     pub(in crate) global_visibility: Cell<bool>,
+    pub(in crate) global_enabled: Cell<bool>,
+    /// Whether global data (transform, visibility, etc.) of the node and its descendants
+    /// must be recalculated on next [`Graph::update_hierarchical_data`] call.
+    pub(in crate) dirty: Cell<bool>,
     pub(in crate) parent: Handle<Node>,
     pub(in crate) children: Vec<Handle<Node>>,
     pub(in crate) physics_binding: PhysicsBinding,
     name: String,
+    layout: Layout,
+    bounds_override: Option<Rect<f32>>,
+    pub(in crate) id: Uuid,
+    /// Stable id of a node this node was copied from, see [`Graph::reapply_prefab`].
+    pub(in crate) prefab_source: Option<Uuid>,
+    /// Overrides of nodes of prefab instance, stored on instance root only.
+    pub(in crate) prefab_overrides: Vec<PrefabOverride>,
+    pub(in crate) rotation_constraint: Option<RotationConstraint>,
+    pub(in crate) change_threshold: Option<ChangeThreshold>,
+    /// Global position and rotation of the node when its change was reported last time, used
+    /// only with change threshold.
+    pub(in crate) reported_state: Cell<Option<(Vector2<f32>, f32)>>,
+    user_data: Option<Box<dyn Any + Send>>,
+    /// Runtime-only, never saved and never copied.
+    pub(in crate) update_callback: Option<UpdateCallback>,
+    /// Runtime-only, never saved and never copied.
+    pub(in crate) transform_watchers: Vec<TransformWatcher>,
+    /// Number of graph frame when the node was added to the graph, diagnostics only.
+    pub(in crate) spawn_frame: u64,
+    /// Whether global transform of the node was calculated as a part of static subtree, so
+    /// any further movement is reported. Diagnostics only.
+    pub(in crate) static_baked: Cell<bool>,
+    /// Runtime-only, never saved and never copied.
+    pub(in crate) gizmo: Option<Gizmo>,
+}
+
+impl Visit for Base {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.transform.visit("Transform", visitor)?;
+        self.global_transform.visit("GlobalTransform", visitor)?;
+        if self.flags.visit("Flags", visitor).is_err() && visitor.is_reading() {
+            // Backward compatibility: visibility was stored as a separate field.
+            let mut visibility = true;
+            visibility.visit("Visibility", visitor)?;
+            self.flags.set(NodeFlags::VISIBLE, visibility);
+        }
+        self.global_visibility.visit("GlobalVisibility", visitor)?;
+        if self.global_enabled.visit("GlobalEnabled", visitor).is_err() && visitor.is_reading() {
+            // Backward compatibility, it is recalculated on next update anyway.
+            self.global_enabled.set(true);
+        }
+        self.parent.visit("Parent", visitor)?;
+        self.children.visit("Children", visitor)?;
+        self.physics_binding.visit("PhysicsBinding", visitor)?;
+        self.name.visit("Name", visitor)?;
+        // Fields below could be missing in files saved by older versions. Nodes without stored
+        // id keep the fresh one they got on creation.
+        visit_optional(&mut self.layout, "Layout", visitor)?;
+        visit_optional(&mut self.bounds_override, "BoundsOverride", visitor)?;
+        visit_optional(&mut self.id, "Id", visitor)?;
+        visit_optional(&mut self.prefab_source, "PrefabSource", visitor)?;
+        visit_optional(&mut self.prefab_overrides, "PrefabOverrides", visitor)?;
+        visit_optional(&mut self.rotation_constraint, "RotationConstraint", visitor)?;
+        visit_optional(&mut self.change_threshold, "ChangeThreshold", visitor)?;
+
+        visitor.leave_region()
+    }
 }
 
 impl Default for Base {
@@ -26,12 +290,28 @@ impl Default for Base {
         Self {
             transform: Default::default(),
             global_transform: Cell::new(Matrix4::identity()),
-            visibility: true,
+            flags: Default::default(),
             global_visibility: Cell::new(true),
+            global_enabled: Cell::new(true),
+            dirty: Cell::new(true),
             parent: Default::default(),
             children: Default::default(),
             physics_binding: Default::default(),
             name: Default::default(),
+            layout: Default::default(),
+            bounds_override: None,
+            id: Uuid::new_v4(),
+            prefab_source: None,
+            prefab_overrides: Default::default(),
+            rotation_constraint: None,
+            change_threshold: None,
+            reported_state: Cell::new(None),
+            user_data: None,
+            update_callback: None,
+            transform_watchers: Default::default(),
+            spawn_frame: 0,
+            static_baked: Cell::new(false),
+            gizmo: None,
         }
     }
 }
@@ -45,16 +325,153 @@ impl Base {
         &self.name
     }
 
+    /// Returns stable id of the node. Unlike handle, the id does not change when the node
+    /// is moved in memory (for example on save/load), so it can be used to identify a node
+    /// across sessions.
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    /// Returns stable id of a node (usually from another graph) this node was copied from by
+    /// [`Graph::copy_node`] or [`Graph::copy_node_inplace`].
+    pub fn prefab_source(&self) -> Option<Uuid> {
+        self.prefab_source
+    }
+
+    /// Returns overrides of nodes of prefab instance if this node is instance root, see
+    /// [`Graph::set_prefab_override`].
+    pub fn prefab_overrides(&self) -> &[PrefabOverride] {
+        &self.prefab_overrides
+    }
+
     pub fn parent(&self) -> Handle<Node> {
         self.parent
     }
 
+    /// Returns current rotation constraint of the node, see [`Graph::set_rotation_constraint`].
+    pub fn rotation_constraint(&self) -> Option<RotationConstraint> {
+        self.rotation_constraint
+    }
+
+    /// Returns current layout of children of the node.
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Sets how the node arranges its children. Children are positioned on next
+    /// [`Graph::update_hierarchical_data`] call.
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+        self.dirty.set(true);
+    }
+
     pub fn global_visibility(&self) -> bool {
         self.global_visibility.get()
     }
 
     pub fn visibility(&self) -> bool {
-        self.visibility
+        self.flags.contains(NodeFlags::VISIBLE)
+    }
+
+    pub fn set_visibility(&mut self, visibility: bool) {
+        self.flags.set(NodeFlags::VISIBLE, visibility);
+        self.dirty.set(true);
+    }
+
+    /// Returns true if the node itself is enabled, it does not take ancestors into account.
+    pub fn enabled(&self) -> bool {
+        self.flags.contains(NodeFlags::ENABLED)
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.flags.set(NodeFlags::ENABLED, enabled);
+        self.dirty.set(true);
+    }
+
+    /// Returns true if the node and all its ancestors are enabled. Calculated in
+    /// [`Graph::update_hierarchical_data`].
+    pub fn global_enabled(&self) -> bool {
+        self.global_enabled.get()
+    }
+
+    pub fn cast_shadow(&self) -> bool {
+        self.flags.contains(NodeFlags::CAST_SHADOW)
+    }
+
+    pub fn set_cast_shadow(&mut self, cast_shadow: bool) {
+        self.flags.set(NodeFlags::CAST_SHADOW, cast_shadow);
+    }
+
+    pub fn frozen(&self) -> bool {
+        self.flags.contains(NodeFlags::FROZEN)
+    }
+
+    pub fn set_frozen(&mut self, frozen: bool) {
+        self.flags.set(NodeFlags::FROZEN, frozen);
+        self.dirty.set(true);
+    }
+
+    /// Returns true if the node itself is marked as screen-space one. Use
+    /// [`Graph::is_in_screen_space`] to check ancestors too.
+    pub fn screen_space(&self) -> bool {
+        self.flags.contains(NodeFlags::SCREEN_SPACE)
+    }
+
+    pub fn set_screen_space(&mut self, screen_space: bool) {
+        self.flags.set(NodeFlags::SCREEN_SPACE, screen_space);
+    }
+
+    /// Returns true if the node clips its descendants by its bounds.
+    pub fn clip(&self) -> bool {
+        self.flags.contains(NodeFlags::CLIP)
+    }
+
+    pub fn set_clip(&mut self, clip: bool) {
+        self.flags.set(NodeFlags::CLIP, clip);
+    }
+
+    /// Returns true if the node can be hit by [`Graph::pick`]. Pickable nodes are still
+    /// skipped by picking when they're invisible.
+    pub fn pickable(&self) -> bool {
+        !self.flags.contains(NodeFlags::NOT_PICKABLE)
+    }
+
+    pub fn set_pickable(&mut self, pickable: bool) {
+        self.flags.set(NodeFlags::NOT_PICKABLE, !pickable);
+    }
+
+    /// Sets bounds (in local coordinates) that are used instead of intrinsic size of the
+    /// node (see [`Node::local_bounds`]) for picking, world bounds, layout, etc. Useful when
+    /// interaction area differs from visual size of a node. `None` restores intrinsic bounds.
+    pub fn set_bounds_override(&mut self, bounds: Option<Rect<f32>>) {
+        self.bounds_override = bounds;
+        self.dirty.set(true);
+    }
+
+    pub fn bounds_override(&self) -> Option<Rect<f32>> {
+        self.bounds_override
+    }
+
+    pub fn change_threshold(&self) -> Option<ChangeThreshold> {
+        self.change_threshold
+    }
+
+    pub fn gizmo(&self) -> Option<&Gizmo> {
+        self.gizmo.as_ref()
+    }
+
+    /// Returns true if the node itself is marked as static, see [`Graph::set_static`].
+    pub fn is_static(&self) -> bool {
+        self.flags.contains(NodeFlags::STATIC)
+    }
+
+    pub fn flags(&self) -> NodeFlags {
+        self.flags
+    }
+
+    pub fn set_flags(&mut self, flags: NodeFlags) {
+        self.flags = flags;
+        self.dirty.set(true);
     }
 
     pub fn global_position(&self) -> Vector2<f32> {
@@ -80,7 +497,10 @@ impl Base {
         &self.transform
     }
 
+    /// Returns mutable reference to local transform of the node. The node is marked as
+    /// dirty, so its global transform will be recalculated on next update.
     pub fn local_transform_mut(&mut self) -> &mut Transform {
+        self.dirty.set(true);
         &mut self.transform
     }
 
@@ -95,14 +515,72 @@ impl Base {
     pub fn physics_binding(&self) -> PhysicsBinding {
         self.physics_binding
     }
+
+    /// Attaches arbitrary data to the node, previous data (if any) is dropped.
+    ///
+    /// # Notes
+    ///
+    /// User data is runtime-only, it is **not** serialized, so it won't survive save/load.
+    pub fn set_user_data<T: Any + Send>(&mut self, data: T) {
+        self.user_data = Some(Box::new(data));
+    }
+
+    /// Returns reference to user data if it has type `T`.
+    pub fn user_data<T: Any>(&self) -> Option<&T> {
+        self.user_data
+            .as_ref()
+            .and_then(|data| data.downcast_ref::<T>())
+    }
+
+    /// Returns mutable reference to user data if it has type `T`.
+    pub fn user_data_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.user_data
+            .as_mut()
+            .and_then(|data| data.downcast_mut::<T>())
+    }
+
+    /// Detaches user data from the node and returns it.
+    pub fn take_user_data(&mut self) -> Option<Box<dyn Any + Send>> {
+        self.user_data.take()
+    }
+
+    /// Moves runtime-only data (user data, update callback, transform watchers, gizmo) from
+    /// other node to this one.
+    pub(in crate) fn take_runtime_data(&mut self, other: &mut Base) {
+        self.user_data = other.user_data.take();
+        self.update_callback = other.update_callback.take();
+        self.transform_watchers = std::mem::take(&mut other.transform_watchers);
+        self.gizmo = other.gizmo.take();
+    }
+
+    /// Shallow copy of node data. You should never use this directly, shallow copy
+    /// will produce invalid node in most cases!
+    pub fn raw_copy(&self) -> Self {
+        Self {
+            transform: self.transform.clone(),
+            global_transform: self.global_transform.clone(),
+            flags: self.flags,
+            global_visibility: self.global_visibility.clone(),
+            global_enabled: self.global_enabled.clone(),
+            physics_binding: self.physics_binding,
+            name: self.name.clone(),
+            layout: self.layout,
+            bounds_override: self.bounds_override,
+            change_threshold: self.change_threshold,
+            // Rest of data is *not* copied! Copy gets its own id.
+            ..Default::default()
+        }
+    }
 }
 
 pub struct BaseBuilder {
     transform: Transform,
     children: Vec<Handle<Node>>,
     name: String,
-    visibility: bool,
+    flags: NodeFlags,
     physics_binding: PhysicsBinding,
+    layout: Layout,
+    bounds_override: Option<Rect<f32>>,
 }
 
 impl Default for BaseBuilder {
@@ -117,8 +595,10 @@ impl BaseBuilder {
             transform: Default::default(),
             children: Default::default(),
             name: "Base".to_string(),
-            visibility: true,
+            flags: Default::default(),
             physics_binding: Default::default(),
+            layout: Default::default(),
+            bounds_override: None,
         }
     }
 
@@ -146,7 +626,37 @@ impl BaseBuilder {
     }
 
     pub fn with_visibility(mut self, visibility: bool) -> Self {
-        self.visibility = visibility;
+        self.flags.set(NodeFlags::VISIBLE, visibility);
+        self
+    }
+
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.flags.set(NodeFlags::ENABLED, enabled);
+        self
+    }
+
+    pub fn with_screen_space(mut self, screen_space: bool) -> Self {
+        self.flags.set(NodeFlags::SCREEN_SPACE, screen_space);
+        self
+    }
+
+    pub fn with_clip(mut self, clip: bool) -> Self {
+        self.flags.set(NodeFlags::CLIP, clip);
+        self
+    }
+
+    pub fn with_pickable(mut self, pickable: bool) -> Self {
+        self.flags.set(NodeFlags::NOT_PICKABLE, !pickable);
+        self
+    }
+
+    pub fn with_bounds_override(mut self, bounds: Rect<f32>) -> Self {
+        self.bounds_override = Some(bounds);
+        self
+    }
+
+    pub fn with_flags(mut self, flags: NodeFlags) -> Self {
+        self.flags = flags;
         self
     }
 
@@ -155,16 +665,37 @@ impl BaseBuilder {
         self
     }
 
+    pub fn with_layout(mut self, layout: Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+
     pub fn build_base(self) -> Base {
         Base {
             transform: self.transform,
             global_transform: Cell::new(Matrix4::identity()),
-            visibility: self.visibility,
+            flags: self.flags,
             global_visibility: Cell::new(false),
+            global_enabled: Cell::new(false),
+            dirty: Cell::new(true),
             parent: Default::default(),
             children: self.children,
             name: self.name,
             physics_binding: self.physics_binding,
+            layout: self.layout,
+            bounds_override: self.bounds_override,
+            id: Uuid::new_v4(),
+            prefab_source: None,
+            prefab_overrides: Default::default(),
+            rotation_constraint: None,
+            change_threshold: None,
+            reported_state: Cell::new(None),
+            user_data: None,
+            update_callback: None,
+            transform_watchers: Default::default(),
+            spawn_frame: 0,
+            static_baked: Cell::new(false),
+            gizmo: None,
         }
     }
 
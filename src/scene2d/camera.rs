@@ -1,20 +1,94 @@
 use crate::{
-    core::{algebra::Matrix4, algebra::Vector2, math::Rect, pool::Handle, visitor::prelude::*},
+    core::{
+        algebra::{Matrix4, Point3, Vector2, Vector3},
+        math::Rect,
+        pool::Handle,
+        rand::{self, Rng},
+        visitor::prelude::*,
+    },
     scene2d::{
         base::{Base, BaseBuilder},
         graph::Graph,
         node::Node,
+        visit_optional,
     },
 };
 use std::ops::{Deref, DerefMut};
 
-#[derive(Visit)]
+/// A single shake effect that was added by [`Camera::add_shake`].
+#[derive(Clone, Debug)]
+struct Shake {
+    intensity: f32,
+    duration: f32,
+    elapsed: f32,
+    frequency: Vector2<f32>,
+    phase: Vector2<f32>,
+}
+
+impl Shake {
+    fn offset(&self) -> Vector2<f32> {
+        // Amplitude linearly decays to zero over the duration of the shake.
+        let amplitude = self.intensity * (1.0 - self.elapsed / self.duration).max(0.0);
+        Vector2::new(
+            (self.elapsed * self.frequency.x + self.phase.x).sin(),
+            (self.elapsed * self.frequency.y + self.phase.y).sin(),
+        )
+        .scale(amplitude)
+    }
+}
+
+/// Defines how projection of a camera adapts to the size of render target.
+#[derive(Copy, Clone, Debug, PartialEq, Visit)]
+pub enum ScalingMode {
+    /// One world unit is one pixel, so bigger render target shows bigger part of the world.
+    Pixels,
+    /// Visible area always has given height (in world units), its width depends on aspect
+    /// ratio of the viewport.
+    FixedHeight { height: f32 },
+    /// Visible area always has given width (in world units), its height depends on aspect
+    /// ratio of the viewport.
+    FixedWidth { width: f32 },
+    /// Visible area is exactly `width` x `height` world units. Aspect ratio is kept by
+    /// shrinking the viewport (letterboxing), see [`Camera::viewport_pixels`].
+    Fit { width: f32, height: f32 },
+}
+
+impl Default for ScalingMode {
+    fn default() -> Self {
+        Self::Pixels
+    }
+}
+
 pub struct Camera {
     base: Base,
     viewport: Rect<f32>,
+    scaling_mode: ScalingMode,
+    zoom: f32,
     view_matrix: Matrix4<f32>,
     projection_matrix: Matrix4<f32>,
-    enabled: bool,
+    shakes: Vec<Shake>,
+}
+
+impl Visit for Camera {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.base.visit("Base", visitor)?;
+        self.viewport.visit("Viewport", visitor)?;
+        visit_optional(&mut self.scaling_mode, "ScalingMode", visitor)?;
+        visit_optional(&mut self.zoom, "Zoom", visitor)?;
+        self.view_matrix.visit("ViewMatrix", visitor)?;
+        self.projection_matrix.visit("ProjectionMatrix", visitor)?;
+        if visitor.is_reading() {
+            // Backward compatibility: cameras had their own enabled flag.
+            let mut enabled = true;
+            if enabled.visit("Enabled", visitor).is_ok() {
+                self.base.set_enabled(enabled);
+            }
+        }
+
+        visitor.leave_region()
+    }
 }
 
 impl Default for Camera {
@@ -22,9 +96,11 @@ impl Default for Camera {
         Self {
             base: Default::default(),
             viewport: Rect::new(0.0, 0.0, 1.0, 1.0),
+            scaling_mode: Default::default(),
+            zoom: 1.0,
             view_matrix: Matrix4::identity(),
             projection_matrix: Matrix4::identity(),
-            enabled: true,
+            shakes: Default::default(),
         }
     }
 }
@@ -44,54 +120,201 @@ impl DerefMut for Camera {
 }
 
 impl Camera {
+    const SHAKE_FREQUENCY_MIN: f32 = 40.0;
+    const SHAKE_FREQUENCY_MAX: f32 = 60.0;
+
     /// Calculates viewport rectangle in pixels based on internal resolution-independent
-    /// viewport. It is useful when you need to get real viewport rectangle in pixels.
+    /// viewport. It is useful when you need to get real viewport rectangle in pixels. With
+    /// [`ScalingMode::Fit`] the rectangle is shrunk and centered to keep aspect ratio.
     #[inline]
     pub fn viewport_pixels(&self, frame_size: Vector2<f32>) -> Rect<i32> {
+        let mut position = Vector2::new(
+            self.viewport.x() * frame_size.x,
+            self.viewport.y() * frame_size.y,
+        );
+        let mut size = Vector2::new(
+            self.viewport.w() * frame_size.x,
+            self.viewport.h() * frame_size.y,
+        );
+        if let ScalingMode::Fit { width, height } = self.scaling_mode {
+            if width > 0.0 && height > 0.0 {
+                let scale = (size.x / width).min(size.y / height);
+                let fitted = Vector2::new(width * scale, height * scale);
+                position += (size - fitted).scale(0.5);
+                size = fitted;
+            }
+        }
         Rect::new(
-            (self.viewport.x() * frame_size.x) as i32,
-            (self.viewport.y() * frame_size.y) as i32,
-            (self.viewport.w() * frame_size.x) as i32,
-            (self.viewport.h() * frame_size.y) as i32,
+            position.x as i32,
+            position.y as i32,
+            size.x as i32,
+            size.y as i32,
         )
     }
 
+    /// Shallow copy of node data. You should never use this directly, shallow copy
+    /// will produce invalid node in most cases!
+    pub fn raw_copy(&self) -> Self {
+        Self {
+            base: self.base.raw_copy(),
+            viewport: self.viewport,
+            scaling_mode: self.scaling_mode,
+            zoom: self.zoom,
+            view_matrix: self.view_matrix,
+            projection_matrix: self.projection_matrix,
+            // Shakes are not copied, they're temporary effects.
+            shakes: Default::default(),
+        }
+    }
+
     pub fn set_viewport(&mut self, viewport: Rect<f32>) {
         self.viewport = viewport;
     }
 
+    /// Sets how projection of the camera adapts to the size of render target, takes effect on
+    /// next [`Self::update`].
+    pub fn set_scaling_mode(&mut self, scaling_mode: ScalingMode) {
+        self.scaling_mode = scaling_mode;
+    }
+
+    pub fn scaling_mode(&self) -> ScalingMode {
+        self.scaling_mode
+    }
+
+    /// Returns size (in world units, without zoom) of the area that is visible through the
+    /// camera for a render target of given size, see [`ScalingMode`].
+    pub fn visible_size(&self, render_target_size: Vector2<f32>) -> Vector2<f32> {
+        let viewport = self.viewport_pixels(render_target_size);
+        let aspect = if viewport.h() > 0 {
+            viewport.w() as f32 / viewport.h() as f32
+        } else {
+            1.0
+        };
+        match self.scaling_mode {
+            ScalingMode::Pixels => render_target_size,
+            ScalingMode::FixedHeight { height } => Vector2::new(height * aspect, height),
+            ScalingMode::FixedWidth { width } => Vector2::new(width, width / aspect),
+            ScalingMode::Fit { width, height } => Vector2::new(width, height),
+        }
+    }
+
+    /// Sets zoom of the camera - amount of pixels per world unit. Zoom is applied around the
+    /// position of the camera (left-top corner of the view) and affects only the view matrix,
+    /// so screen-space nodes are not zoomed. Values that are not positive are ignored.
+    pub fn set_zoom(&mut self, zoom: f32) {
+        if zoom > 0.0 {
+            self.zoom = zoom;
+        }
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    /// Sets position (in parent space, which is world space for cameras attached to root) and
+    /// zoom of the camera so the `rect` (in world coordinates) is centered and entirely
+    /// visible in a view of `viewport` size (in pixels), with at least `margin` world units
+    /// around it. Aspect ratio is preserved, so the rect won't be distorted but there can be
+    /// extra area along one of the axes. Degenerate rects are only centered, zoom stays the
+    /// same. With scaling modes other than [`ScalingMode::Pixels`] pass
+    /// [`Self::visible_size`] as `viewport`.
+    pub fn fit_rect(&mut self, rect: Rect<f32>, viewport: Vector2<f32>, margin: f32) {
+        let width = rect.w() + 2.0 * margin;
+        let height = rect.h() + 2.0 * margin;
+        if width > 0.0 && height > 0.0 && viewport.x > 0.0 && viewport.y > 0.0 {
+            self.zoom = (viewport.x / width).min(viewport.y / height);
+        }
+
+        let center = Vector2::new(rect.x() + rect.w() * 0.5, rect.y() + rect.h() * 0.5);
+        let visible = viewport.scale(1.0 / self.zoom);
+        self.local_transform_mut()
+            .set_position(center - visible.scale(0.5));
+    }
+
+    /// Projects a point from world coordinates to screen coordinates (in pixels, relative to
+    /// left-top corner of a frame of given size), viewport of the camera is taken into account.
+    /// Result is correct only after [`Self::update`] with the same frame size.
+    pub fn world_to_screen(&self, point: Vector2<f32>, frame_size: Vector2<f32>) -> Vector2<f32> {
+        let ndc = self
+            .view_projection_matrix()
+            .transform_point(&Point3::new(point.x, point.y, 0.0));
+        let viewport = self.viewport_pixels(frame_size);
+        Vector2::new(
+            viewport.x() as f32 + (ndc.x + 1.0) * 0.5 * viewport.w() as f32,
+            viewport.y() as f32 + (1.0 - ndc.y) * 0.5 * viewport.h() as f32,
+        )
+    }
+
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        self.view_matrix
+    }
+
+    pub fn projection_matrix(&self) -> Matrix4<f32> {
+        self.projection_matrix
+    }
+
     pub fn view_projection_matrix(&self) -> Matrix4<f32> {
         self.projection_matrix * self.view_matrix
     }
 
-    pub fn update(&mut self, render_target_size: Vector2<f32>) {
-        self.projection_matrix = Matrix4::new_orthographic(
-            0.0,
-            render_target_size.x,
-            render_target_size.y,
-            0.0,
-            0.0,
-            1.0,
-        );
+    /// Adds new shake effect with given intensity (maximum offset in world units) and
+    /// duration (in seconds). Overlapping shakes are summed. Shake affects only view matrix
+    /// of the camera, its position in the graph stays untouched.
+    pub fn add_shake(&mut self, intensity: f32, duration: f32) {
+        if duration <= 0.0 {
+            return;
+        }
 
-        self.view_matrix = self
-            .global_transform()
-            .try_inverse()
-            .unwrap_or_else(Matrix4::identity);
+        let mut rng = rand::thread_rng();
+        self.shakes.push(Shake {
+            intensity,
+            duration,
+            elapsed: 0.0,
+            frequency: Vector2::new(
+                rng.gen_range(Self::SHAKE_FREQUENCY_MIN..Self::SHAKE_FREQUENCY_MAX),
+                rng.gen_range(Self::SHAKE_FREQUENCY_MIN..Self::SHAKE_FREQUENCY_MAX),
+            ),
+            phase: Vector2::new(
+                rng.gen_range(0.0..std::f32::consts::TAU),
+                rng.gen_range(0.0..std::f32::consts::TAU),
+            ),
+        });
     }
 
-    pub fn enabled(&self) -> bool {
-        self.enabled
+    /// Returns current offset of the view caused by active shakes.
+    pub fn shake_offset(&self) -> Vector2<f32> {
+        self.shakes
+            .iter()
+            .fold(Vector2::default(), |offset, shake| offset + shake.offset())
     }
 
-    pub fn set_enabled(&mut self, enabled: bool) {
-        self.enabled = enabled;
+    /// Updates matrices and time-based effects (shakes) of the camera. `dt` is time (in
+    /// seconds) passed since previous update. Called automatically by [`Graph::update`].
+    pub fn update(&mut self, render_target_size: Vector2<f32>, dt: f32) {
+        for shake in self.shakes.iter_mut() {
+            shake.elapsed += dt;
+        }
+        self.shakes.retain(|shake| shake.elapsed < shake.duration);
+
+        let visible_size = self.visible_size(render_target_size);
+        self.projection_matrix =
+            Matrix4::new_orthographic(0.0, visible_size.x, visible_size.y, 0.0, 0.0, 1.0);
+
+        let shake_offset = self.shake_offset();
+        let view = (Matrix4::new_translation(&Vector3::new(shake_offset.x, shake_offset.y, 0.0))
+            * self.global_transform())
+        .try_inverse()
+        .unwrap_or_else(Matrix4::identity);
+        self.view_matrix =
+            Matrix4::new_nonuniform_scaling(&Vector3::new(self.zoom, self.zoom, 1.0)) * view;
     }
 }
 
 pub struct CameraBuilder {
     base_builder: BaseBuilder,
     viewport: Rect<f32>,
+    scaling_mode: ScalingMode,
+    zoom: f32,
     enabled: bool,
 }
 
@@ -100,6 +323,8 @@ impl CameraBuilder {
         Self {
             base_builder,
             viewport: Rect::new(0.0, 0.0, 1.0, 1.0),
+            scaling_mode: Default::default(),
+            zoom: 1.0,
             enabled: true,
         }
     }
@@ -109,6 +334,16 @@ impl CameraBuilder {
         self
     }
 
+    pub fn with_scaling_mode(mut self, scaling_mode: ScalingMode) -> Self {
+        self.scaling_mode = scaling_mode;
+        self
+    }
+
+    pub fn with_zoom(mut self, zoom: f32) -> Self {
+        self.zoom = zoom;
+        self
+    }
+
     pub fn with_enabled(mut self, enabled: bool) -> Self {
         self.enabled = enabled;
         self
@@ -116,11 +351,67 @@ impl CameraBuilder {
 
     pub fn build(self, graph: &mut Graph) -> Handle<Node> {
         graph.add_node(Node::Camera(Camera {
-            base: self.base_builder.build_base(),
+            base: self.base_builder.with_enabled(self.enabled).build_base(),
             viewport: self.viewport,
+            scaling_mode: self.scaling_mode,
+            zoom: if self.zoom > 0.0 { self.zoom } else { 1.0 },
             view_matrix: Matrix4::identity(),
             projection_matrix: Default::default(),
-            enabled: self.enabled,
+            shakes: Default::default(),
         }))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{algebra::Vector2, math::Rect},
+        scene2d::{
+            base::BaseBuilder,
+            camera::{CameraBuilder, ScalingMode},
+            graph::Graph,
+        },
+    };
+
+    #[test]
+    fn camera_scaling_mode_test() {
+        let mut graph = Graph::new();
+        let camera = CameraBuilder::new(BaseBuilder::new())
+            .with_scaling_mode(ScalingMode::FixedHeight { height: 10.0 })
+            .build(&mut graph);
+        let size = Vector2::new(200.0, 100.0);
+        let camera = graph[camera].as_camera_mut();
+        assert_eq!(camera.visible_size(size), Vector2::new(20.0, 10.0));
+
+        // Viewport is letterboxed to keep aspect ratio of the visible area.
+        camera.set_scaling_mode(ScalingMode::Fit {
+            width: 10.0,
+            height: 10.0,
+        });
+        assert_eq!(camera.visible_size(size), Vector2::new(10.0, 10.0));
+        assert_eq!(camera.viewport_pixels(size), Rect::new(50, 0, 100, 100));
+
+        camera.set_scaling_mode(ScalingMode::Pixels);
+        assert_eq!(camera.visible_size(size), size);
+        assert_eq!(camera.viewport_pixels(size), Rect::new(0, 0, 200, 100));
+    }
+
+    #[test]
+    fn camera_fit_rect_test() {
+        let mut graph = Graph::new();
+        let camera = CameraBuilder::new(BaseBuilder::new()).build(&mut graph);
+        let camera = graph[camera].as_camera_mut();
+
+        camera.fit_rect(
+            Rect::new(0.0, 0.0, 10.0, 20.0),
+            Vector2::new(80.0, 80.0),
+            0.0,
+        );
+        assert_eq!(camera.zoom(), 4.0);
+        // Rect is centered along the axis with extra space.
+        assert_eq!(camera.local_transform().position(), Vector2::new(-5.0, 0.0));
+
+        camera.set_zoom(-1.0);
+        assert_eq!(camera.zoom(), 4.0);
+    }
+}
@@ -0,0 +1,86 @@
+//! Resumable iteration over a 2D graph, see [`GraphCursor`].
+
+use crate::{
+    core::{pool::Handle, uuid::Uuid},
+    scene2d::{graph::Graph, node::Node},
+};
+use std::collections::VecDeque;
+
+/// Cursor that walks over nodes of a graph in [render order](Graph::render_order) in batches,
+/// so heavy processing of huge graphs can be spread over many frames. The cursor does not
+/// borrow the graph, nodes are remembered by their stable ids, so the graph can be freely
+/// modified between batches: nodes that were removed are silently skipped, nodes that were
+/// added during a pass are visited in the next one. Root is never yielded.
+#[derive(Clone, Debug, Default)]
+pub struct GraphCursor {
+    pending: VecDeque<Uuid>,
+}
+
+impl GraphCursor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns up to `max` handles of alive nodes that weren't visited in current pass yet.
+    /// If current pass is finished, new one is started from the beginning of the graph.
+    /// Returned list can be shorter than `max` (or even empty, if remaining nodes were removed)
+    /// at the end of a pass.
+    pub fn next_batch(&mut self, graph: &Graph, max: usize) -> Vec<Handle<Node>> {
+        if self.pending.is_empty() {
+            self.pending = graph
+                .render_order(graph.get_root())
+                .into_iter()
+                .skip(1)
+                .map(|handle| graph[handle].id())
+                .collect();
+        }
+
+        let mut batch = Vec::with_capacity(max.min(self.pending.len()));
+        while batch.len() < max {
+            match self.pending.pop_front() {
+                Some(id) => {
+                    let handle = graph.node_by_id(id);
+                    if handle.is_some() {
+                        batch.push(handle);
+                    }
+                }
+                None => break,
+            }
+        }
+        batch
+    }
+
+    /// Returns true if current pass is finished, so next batch will start a new one.
+    pub fn is_pass_finished(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Drops current pass, next batch will start from the beginning of a graph.
+    pub fn reset(&mut self) {
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::scene2d::{base::BaseBuilder, cursor::GraphCursor, graph::Graph};
+
+    #[test]
+    fn cursor_batches_test() {
+        let mut graph = Graph::new();
+        let a = BaseBuilder::new().build(&mut graph);
+        let b = BaseBuilder::new().build(&mut graph);
+        let c = BaseBuilder::new().build(&mut graph);
+
+        let mut cursor = GraphCursor::new();
+        assert_eq!(cursor.next_batch(&graph, 2), vec![a, b]);
+        assert!(!cursor.is_pass_finished());
+
+        // Removed nodes are skipped, added ones wait for next pass.
+        graph.remove_node(c);
+        let d = BaseBuilder::new().build(&mut graph);
+        assert!(cursor.next_batch(&graph, 2).is_empty());
+        assert!(cursor.is_pass_finished());
+        assert_eq!(cursor.next_batch(&graph, 10), vec![a, b, d]);
+    }
+}
@@ -0,0 +1,64 @@
+//! Difference between two 2D graphs, see [`Graph::diff`](crate::scene2d::graph::Graph::diff).
+//!
+//! Nodes are matched by their stable ids, so a diff computed against one graph can be applied
+//! to any other graph that shares the ids (for example, a scene loaded from the same file).
+//! Nil id always stands for root node of a graph.
+
+use crate::{
+    core::uuid::Uuid,
+    scene2d::{node::Node, transform::Transform},
+};
+
+/// A node that exists in new graph, but not in old one.
+pub struct AddedNode {
+    /// Stable id of the node.
+    pub id: Uuid,
+    /// Stable id of parent node, nil if the node is attached to root.
+    pub parent: Uuid,
+    /// Shallow copy of the node, without children.
+    pub node: Node,
+}
+
+/// A node that exists in both graphs, but has different properties. Only changed properties
+/// are `Some`.
+#[derive(Default)]
+pub struct ChangedNode {
+    /// Stable id of the node.
+    pub id: Uuid,
+    /// New local transform of the node.
+    pub local_transform: Option<Transform>,
+    /// New name of the node.
+    pub name: Option<String>,
+    /// Stable id of new parent of the node, nil if the node should be attached to root.
+    pub parent: Option<Uuid>,
+}
+
+impl ChangedNode {
+    fn is_empty(&self) -> bool {
+        self.local_transform.is_none() && self.name.is_none() && self.parent.is_none()
+    }
+}
+
+/// Set of changes that turns one graph into another.
+#[derive(Default)]
+pub struct GraphDiff {
+    /// Nodes to add, parents always come before their children.
+    pub added: Vec<AddedNode>,
+    /// Stable ids of nodes to remove.
+    pub removed: Vec<Uuid>,
+    /// Nodes that exist in both graphs, but have different properties.
+    pub changed: Vec<ChangedNode>,
+}
+
+impl GraphDiff {
+    /// Returns true if there is no difference between graphs.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    pub(in crate) fn push_changed(&mut self, changed: ChangedNode) {
+        if !changed.is_empty() {
+            self.changed.push(changed);
+        }
+    }
+}
@@ -1,21 +1,193 @@
-use crate::core::algebra::{Rotation2, UnitComplex, Vector3};
-use crate::scene2d::transform::TransformBuilder;
 use crate::{
     core::{
-        algebra::{Matrix4, Vector2},
+        algebra::{Matrix4, Point3, Rotation2, UnitComplex, Vector2, Vector3},
+        math::Rect,
         pool::{Handle, Pool, Ticket},
+        uuid::Uuid,
         visitor::prelude::*,
     },
-    scene2d::node::Node,
+    scene2d::{
+        base::{
+            BaseBuilder, ChangeThreshold, Gizmo, Layout, NodeFlags, PrefabOverride, PrefabProperty,
+            RotationConstraint, TransformWatcher, UpdateCallback,
+        },
+        bounding_rect,
+        diff::{AddedNode, ChangedNode, GraphDiff},
+        intersect_rects,
+        node::{Node, NodeKind},
+        transform::{Transform, TransformBuilder},
+        visit_optional,
+    },
+    utils::log::{Log, MessageKind},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::{Index, IndexMut},
 };
-use std::ops::{Index, IndexMut};
 
-#[derive(Default, Visit)]
+/// Anything that stores handles to nodes of a graph and must fix them when the graph changes
+/// memory layout of its nodes (see [`Graph::compact`]).
+pub trait RemapHandles {
+    /// Replaces every stored handle with a new one from given old-to-new map. Handles that
+    /// are not in the map must become [`Handle::NONE`].
+    fn remap(&mut self, map: &HashMap<Handle<Node>, Handle<Node>>);
+}
+
+impl RemapHandles for Handle<Node> {
+    fn remap(&mut self, map: &HashMap<Handle<Node>, Handle<Node>>) {
+        *self = map.get(self).copied().unwrap_or(Handle::NONE);
+    }
+}
+
+impl<T: RemapHandles> RemapHandles for Option<T> {
+    fn remap(&mut self, map: &HashMap<Handle<Node>, Handle<Node>>) {
+        if let Some(value) = self {
+            value.remap(map);
+        }
+    }
+}
+
+impl<T: RemapHandles> RemapHandles for Vec<T> {
+    fn remap(&mut self, map: &HashMap<Handle<Node>, Handle<Node>>) {
+        for item in self.iter_mut() {
+            item.remap(map);
+        }
+    }
+}
+
+/// A reason why nodes can not be borrowed, see [`Graph::try_get_two_mut`].
+#[derive(Debug)]
+pub enum BorrowError {
+    /// Handle does not point to a node.
+    InvalidHandle(Handle<Node>),
+    /// Handles point to the same node, it can not be borrowed mutably twice.
+    Overlap(Handle<Node>),
+}
+
+/// A reason why a graph is not in a consistent state, see [`Graph::validate`].
+#[derive(Debug)]
+pub enum ValidationError {
+    /// Root handle of the graph does not point to a node.
+    InvalidRoot,
+    /// A node (other than root) has a parent handle that does not point to a node.
+    DanglingParent(Handle<Node>),
+    /// A node is not listed in children of its parent, or listed in children of a node that
+    /// is not its parent.
+    ParentChildMismatch(Handle<Node>),
+    /// A node can not be reached from root.
+    Unreachable(Handle<Node>),
+    /// Stable id of a node is not mapped to the node.
+    StaleId(Handle<Node>),
+}
+
+/// A reason why nodes can not be grouped, see [`Graph::group`].
+#[derive(Debug)]
+pub enum GroupError {
+    /// There are no nodes to group.
+    Empty,
+    /// Handle does not point to a node, or points to root.
+    InvalidHandle(Handle<Node>),
+    /// A node has different parent than the first node.
+    DifferentParents(Handle<Node>),
+}
+
+/// A reason why a node can not be linked to a parent, see [`Graph::can_link`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LinkError {
+    /// Child handle does not point to a node.
+    InvalidChild(Handle<Node>),
+    /// Parent handle does not point to a node.
+    InvalidParent(Handle<Node>),
+    /// Root node can not be a child of other node.
+    RootAsChild,
+    /// A node can not be its own parent.
+    SelfLink,
+    /// Parent lies in the subtree of the child, linking would create a cycle.
+    Cycle,
+}
+
+/// Owned copy of complete transform state of a node, see [`Graph::transform_snapshot`] and
+/// [`Graph::restore_transform`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TransformSnapshot {
+    pub position: Vector2<f32>,
+    pub rotation: f32,
+    pub scale: Vector2<f32>,
+    pub pivot: Vector2<f32>,
+    /// Cached global transform at the moment of the snapshot, it is valid only if the graph
+    /// was updated before the snapshot was made.
+    pub global_transform: Matrix4<f32>,
+}
+
+/// Owned copy of a subtree that does not belong to any graph, see
+/// [`Graph::clone_subtree_detached`]. The copy is not affected by changes of the graph it was
+/// made from, so it can be kept (for example in a clipboard) while the graph is modified.
+pub struct DetachedSubtree {
+    /// Copies of nodes in depth-first order, so a parent always goes before its children. First
+    /// node is the root of subtree. Handles stored in the nodes are meaningless.
+    nodes: Vec<Node>,
+    /// Index of parent of every node in `nodes`, `None` for the root of subtree.
+    parents: Vec<Option<usize>>,
+}
+
+impl DetachedSubtree {
+    /// Returns root node of the subtree, `None` if the subtree is empty.
+    pub fn root(&self) -> Option<&Node> {
+        self.nodes.first()
+    }
+
+    /// Returns nodes of the subtree in depth-first order, first node is the root.
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// Returns index (in [`Self::nodes`]) of parent of a node with given index, `None` for the
+    /// root of the subtree.
+    pub fn parent_index(&self, index: usize) -> Option<usize> {
+        self.parents.get(index).copied().flatten()
+    }
+
+    /// Returns amount of nodes in the subtree.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns true if the subtree has no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+/// Decomposes a 2D transformation matrix into position, rotation angle and scale. Skew can not
+/// be represented by the decomposition, so only closest rotation and scale are returned for
+/// skewed matrices. Negative determinant (mirroring) goes to Y scale.
+fn decompose_transform(m: &Matrix4<f32>) -> (Vector2<f32>, f32, Vector2<f32>) {
+    let scale_x = Vector2::new(m[(0, 0)], m[(1, 0)]).norm();
+    let determinant = m[(0, 0)] * m[(1, 1)] - m[(0, 1)] * m[(1, 0)];
+    let scale_y = if scale_x > f32::EPSILON {
+        determinant / scale_x
+    } else {
+        Vector2::new(m[(0, 1)], m[(1, 1)]).norm()
+    };
+    (
+        Vector2::new(m[12], m[13]),
+        m[(1, 0)].atan2(m[(0, 0)]),
+        Vector2::new(scale_x, scale_y),
+    )
+}
+
+#[derive(Default)]
 pub struct Graph {
     pool: Pool<Node>,
     root: Handle<Node>,
-    #[visit(skip)]
     stack: Vec<Handle<Node>>,
+    active_camera: Handle<Node>,
+    id_map: HashMap<Uuid, Handle<Node>>,
+    batch_depth: usize,
+    structure_changed: bool,
+    changed: Vec<Handle<Node>>,
+    transform_epoch: u64,
+    frame: u64,
 }
 
 impl Graph {
@@ -24,11 +196,21 @@ impl Graph {
         let mut pool = Pool::new();
         let mut root = Node::Base(Default::default());
         root.set_name("__ROOT__");
+        let id = root.id();
         let root = pool.spawn(root);
+        let mut id_map = HashMap::new();
+        id_map.insert(id, root);
         Self {
             stack: Vec::new(),
             root,
             pool,
+            active_camera: Handle::NONE,
+            id_map,
+            batch_depth: 0,
+            structure_changed: false,
+            changed: Vec::new(),
+            transform_epoch: 0,
+            frame: 0,
         }
     }
 
@@ -39,7 +221,11 @@ impl Graph {
     pub fn add_node(&mut self, mut node: Node) -> Handle<Node> {
         let children = node.children.clone();
         node.children.clear();
+        let id = node.id();
+        node.spawn_frame = self.frame;
         let handle = self.pool.spawn(node);
+        self.id_map.insert(id, handle);
+        self.structure_changed = true;
         if self.root.is_some() {
             self.link_nodes(handle, self.root);
         }
@@ -50,9 +236,27 @@ impl Graph {
     }
 
     /// Tries to borrow mutable references to two nodes at the same time by given handles. Will
-    /// panic if handles overlaps (points to same node).
+    /// panic if handles overlaps (points to same node) or invalid, see [`Self::try_get_two_mut`]
+    /// for non-panicking version.
     pub fn get_two_mut(&mut self, nodes: (Handle<Node>, Handle<Node>)) -> (&mut Node, &mut Node) {
-        self.pool.borrow_two_mut(nodes)
+        self.try_get_two_mut(nodes).unwrap()
+    }
+
+    /// Tries to borrow mutable references to two nodes at the same time by given handles. Will
+    /// return Err if handles overlaps (points to same node) or any of them is invalid.
+    pub fn try_get_two_mut(
+        &mut self,
+        nodes: (Handle<Node>, Handle<Node>),
+    ) -> Result<(&mut Node, &mut Node), BorrowError> {
+        for &handle in &[nodes.0, nodes.1] {
+            if !self.pool.is_valid_handle(handle) {
+                return Err(BorrowError::InvalidHandle(handle));
+            }
+        }
+        if nodes.0 == nodes.1 {
+            return Err(BorrowError::Overlap(nodes.0));
+        }
+        Ok(self.pool.borrow_two_mut(nodes))
     }
 
     /// Tries to borrow mutable references to three nodes at the same time by given handles. Will
@@ -78,6 +282,201 @@ impl Graph {
         self.root
     }
 
+    /// Sets active camera of the graph. Handle must point to a camera node, otherwise active
+    /// camera will be reset to none.
+    pub fn set_active_camera(&mut self, camera: Handle<Node>) {
+        self.active_camera = if self
+            .pool
+            .try_borrow(camera)
+            .map_or(false, |n| n.is_camera())
+        {
+            camera
+        } else {
+            Handle::NONE
+        };
+    }
+
+    /// Returns handle of active camera of the graph, or [`Handle::NONE`] if there is no
+    /// active camera.
+    pub fn active_camera(&self) -> Handle<Node> {
+        self.active_camera
+    }
+
+    /// Searches a node by its stable id. If nothing was found, [`Handle::NONE`] is returned.
+    pub fn node_by_id(&self, id: Uuid) -> Handle<Node> {
+        self.id_map.get(&id).copied().unwrap_or(Handle::NONE)
+    }
+
+    pub(in crate) fn rebuild_id_map(&mut self) {
+        self.id_map = self
+            .pool
+            .pair_iter()
+            .map(|(handle, node)| (node.id(), handle))
+            .collect();
+    }
+
+    /// Makes sure that the graph has a valid root node. If stored root handle is either none
+    /// or stale (it could happen if a malformed file was loaded), then new `__ROOT__` node will
+    /// be created and every node without a valid parent will be attached to it.
+    pub(in crate) fn ensure_root(&mut self) {
+        if self.pool.is_valid_handle(self.root) {
+            return;
+        }
+
+        let mut root = Node::Base(Default::default());
+        root.set_name("__ROOT__");
+        let id = root.id();
+        let root = self.pool.spawn(root);
+        self.id_map.insert(id, root);
+        self.root = root;
+
+        let orphans = self
+            .pool
+            .pair_iter()
+            .filter(|(handle, node)| *handle != root && !self.pool.is_valid_handle(node.parent()))
+            .map(|(handle, _)| handle)
+            .collect::<Vec<_>>();
+
+        for orphan in orphans {
+            // Parent handle could be stale, it must be reset before linking.
+            self.pool[orphan].parent = Handle::NONE;
+            self.link_nodes(orphan, root);
+        }
+    }
+
+    /// Creates deep copy of node with all children and puts the copy into `dest_graph`.
+    /// Copy gets new stable ids, runtime-only data (user data, etc.) is not copied. Every
+    /// copied node remembers id of its original as [prefab source](Self::reapply_prefab).
+    ///
+    /// Returns tuple where first element is handle to copy of node, and second element -
+    /// old-to-new hash map, which can be used to easily find copy of node by its original.
+    ///
+    /// Filter allows to exclude some nodes from copied hierarchy. It must return false for
+    /// odd nodes. Filtering applied only to descendant nodes.
+    pub fn copy_node<F>(
+        &self,
+        node_handle: Handle<Node>,
+        dest_graph: &mut Graph,
+        filter: &mut F,
+    ) -> (Handle<Node>, HashMap<Handle<Node>, Handle<Node>>)
+    where
+        F: FnMut(Handle<Node>, &Node) -> bool,
+    {
+        let mut old_new_mapping = HashMap::new();
+        let root_handle = self.copy_node_raw(node_handle, dest_graph, &mut old_new_mapping, filter);
+        // References to nodes outside of the copied subtree can't be kept in other graph.
+        for (&old, &new) in old_new_mapping.iter() {
+            let copy = &mut dest_graph.pool[new];
+            copy.copy_references(&self.pool[old]);
+            copy.remap_handles(&old_new_mapping);
+        }
+        (root_handle, old_new_mapping)
+    }
+
+    fn copy_node_raw<F>(
+        &self,
+        node_handle: Handle<Node>,
+        dest_graph: &mut Graph,
+        old_new_mapping: &mut HashMap<Handle<Node>, Handle<Node>>,
+        filter: &mut F,
+    ) -> Handle<Node>
+    where
+        F: FnMut(Handle<Node>, &Node) -> bool,
+    {
+        let src_node = &self.pool[node_handle];
+        let mut copy = src_node.raw_copy();
+        copy.prefab_source = Some(src_node.id());
+        let copy_handle = dest_graph.add_node(copy);
+        old_new_mapping.insert(node_handle, copy_handle);
+        for &child in src_node.children() {
+            if filter(child, &self.pool[child]) {
+                let child_copy = self.copy_node_raw(child, dest_graph, old_new_mapping, filter);
+                dest_graph.link_nodes(child_copy, copy_handle);
+            }
+        }
+        copy_handle
+    }
+
+    /// Does the same as [`Self::copy_node`], but puts the copy into the same graph. Copy of
+    /// the node is attached to the root of the graph.
+    pub fn copy_node_inplace<F>(
+        &mut self,
+        node_handle: Handle<Node>,
+        filter: &mut F,
+    ) -> (Handle<Node>, HashMap<Handle<Node>, Handle<Node>>)
+    where
+        F: FnMut(Handle<Node>, &Node) -> bool,
+    {
+        let mut old_new_mapping = HashMap::new();
+        let root_handle = self.copy_node_inplace_raw(node_handle, &mut old_new_mapping, filter);
+        // References to nodes outside of the copied subtree are kept as is.
+        let mut references = old_new_mapping.clone();
+        for &old in old_new_mapping.keys() {
+            for referenced in self.pool[old].referenced_handles() {
+                references.entry(referenced).or_insert(referenced);
+            }
+        }
+        for (&old, &new) in old_new_mapping.iter() {
+            let (original, copy) = self.get_two_mut((old, new));
+            copy.copy_references(original);
+            copy.remap_handles(&references);
+        }
+        (root_handle, old_new_mapping)
+    }
+
+    fn copy_node_inplace_raw<F>(
+        &mut self,
+        node_handle: Handle<Node>,
+        old_new_mapping: &mut HashMap<Handle<Node>, Handle<Node>>,
+        filter: &mut F,
+    ) -> Handle<Node>
+    where
+        F: FnMut(Handle<Node>, &Node) -> bool,
+    {
+        let mut copy = self.pool[node_handle].raw_copy();
+        copy.prefab_source = Some(self.pool[node_handle].id());
+        let children = self.pool[node_handle].children.clone();
+        let copy_handle = self.add_node(copy);
+        old_new_mapping.insert(node_handle, copy_handle);
+        for child in children {
+            if filter(child, &self.pool[child]) {
+                let child_copy = self.copy_node_inplace_raw(child, old_new_mapping, filter);
+                self.link_nodes(child_copy, copy_handle);
+            }
+        }
+        copy_handle
+    }
+
+    /// Creates `rows * cols` copies of a subtree starting from `template` and places them on
+    /// a grid with given spacing. First copy is placed at the position of the template, rows
+    /// go along Y axis and columns go along X axis of the parent's coordinate system. Copies
+    /// are independent subtrees attached to the parent of the template. Returns handles of
+    /// roots of the copies in row-major order.
+    pub fn instantiate_grid(
+        &mut self,
+        template: Handle<Node>,
+        rows: usize,
+        cols: usize,
+        spacing: Vector2<f32>,
+    ) -> Vec<Handle<Node>> {
+        let parent = self.pool[template].parent();
+        let origin = self.pool[template].local_transform().position();
+        let mut copies = Vec::with_capacity(rows * cols);
+        for row in 0..rows {
+            for col in 0..cols {
+                let (copy, _) = self.copy_node_inplace(template, &mut |_, _| true);
+                if parent.is_some() {
+                    self.link_nodes(copy, parent);
+                }
+                self.pool[copy].local_transform_mut().set_position(
+                    origin + Vector2::new(col as f32 * spacing.x, row as f32 * spacing.y),
+                );
+                copies.push(copy);
+            }
+        }
+        copies
+    }
+
     /// Destroys node and its children recursively.
     ///
     /// # Notes
@@ -95,11 +494,17 @@ impl Graph {
             for &child in self.pool[handle].children().iter() {
                 self.stack.push(child);
             }
-            self.pool.free(handle);
+            let node = self.pool.free(handle);
+            self.id_map.remove(&node.id());
+            if handle == self.active_camera {
+                self.active_camera = Handle::NONE;
+            }
         }
     }
 
     fn unlink_internal(&mut self, node_handle: Handle<Node>) {
+        self.structure_changed = true;
+
         // Replace parent handle of child
         let parent_handle = std::mem::replace(&mut self.pool[node_handle].parent, Handle::NONE);
 
@@ -116,10 +521,73 @@ impl Graph {
     #[inline]
     pub fn link_nodes(&mut self, child: Handle<Node>, parent: Handle<Node>) {
         self.unlink_internal(child);
-        self.pool[child].parent = parent;
+        let child_ref = &mut self.pool[child];
+        child_ref.parent = parent;
+        child_ref.dirty.set(true);
         self.pool[parent].children.push(child);
     }
 
+    /// Links specified child with specified parent and puts the child at given position in
+    /// children list of the parent, so draw order of the child is defined at link time. Index
+    /// is clamped to the length of children list.
+    #[inline]
+    pub fn link_nodes_at(&mut self, child: Handle<Node>, parent: Handle<Node>, index: usize) {
+        self.unlink_internal(child);
+        let child_ref = &mut self.pool[child];
+        child_ref.parent = parent;
+        child_ref.dirty.set(true);
+        let children = &mut self.pool[parent].children;
+        children.insert(index.min(children.len()), child);
+    }
+
+    /// Checks whether [`Self::try_link_nodes`] would succeed for given pair of nodes, without
+    /// modifying the graph. Useful for previews of drag'n'drop operations in editors.
+    pub fn can_link(&self, child: Handle<Node>, parent: Handle<Node>) -> Result<(), LinkError> {
+        if !self.pool.is_valid_handle(child) {
+            Err(LinkError::InvalidChild(child))
+        } else if !self.pool.is_valid_handle(parent) {
+            Err(LinkError::InvalidParent(parent))
+        } else if child == self.root {
+            Err(LinkError::RootAsChild)
+        } else if child == parent {
+            Err(LinkError::SelfLink)
+        } else if self.is_descendant_of(parent, child) {
+            Err(LinkError::Cycle)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checked version of [`Self::link_nodes`], the graph is left untouched if [`Self::can_link`]
+    /// fails for given pair of nodes.
+    pub fn try_link_nodes(
+        &mut self,
+        child: Handle<Node>,
+        parent: Handle<Node>,
+    ) -> Result<(), LinkError> {
+        self.can_link(child, parent)?;
+        self.link_nodes(child, parent);
+        Ok(())
+    }
+
+    /// Links every given child with specified parent, children are added to the end of
+    /// children list of the parent in given order. Each child is unlinked from its current
+    /// parent first, just like [`Self::link_nodes`] does. Every pair is checked by
+    /// [`Self::can_link`] first, the graph is left untouched if any of the checks fails.
+    pub fn link_children(
+        &mut self,
+        parent: Handle<Node>,
+        children: &[Handle<Node>],
+    ) -> Result<(), LinkError> {
+        for &child in children {
+            self.can_link(child, parent)?;
+        }
+        for &child in children {
+            self.link_nodes(child, parent);
+        }
+        Ok(())
+    }
+
     /// Unlinks specified node from its parent and attaches it to root graph node.
     #[inline]
     pub fn unlink_node(&mut self, node_handle: Handle<Node>) {
@@ -130,10 +598,31 @@ impl Graph {
             .set_position(Vector2::default());
     }
 
+    /// Unlinks all children of a node and attaches them to root, local transforms of the
+    /// children are kept. Returns handles of detached children in their former order, so they
+    /// can be linked somewhere else right away. Does nothing and returns empty list for root,
+    /// its children are already attached to root.
+    pub fn detach_children(&mut self, parent: Handle<Node>) -> Vec<Handle<Node>> {
+        if parent == self.root {
+            return Vec::new();
+        }
+        let children = self.pool[parent].children().to_vec();
+        for &child in children.iter() {
+            self.link_nodes(child, self.root);
+        }
+        children
+    }
+
     pub fn capacity(&self) -> usize {
         self.pool.get_capacity()
     }
 
+    /// Returns amount of alive nodes in the graph (including root), unlike [`Self::capacity`]
+    /// vacant entries of internal storage are not counted.
+    pub fn node_count(&self) -> usize {
+        self.pool.alive_count()
+    }
+
     /// Makes new handle from given index. Handle will be none if index was either out-of-bounds
     /// or point to a vacant pool entry.
     pub fn handle_from_index(&self, index: usize) -> Handle<Node> {
@@ -153,26 +642,116 @@ impl Graph {
     }
 
     /// Creates new iterator that iterates over internal collection giving (handle; node) pairs.
+    /// Iteration order is the order of nodes in memory, it depends on allocation history and
+    /// may change after compaction or save/load. Use [`Self::pair_iter_hierarchical`] if you
+    /// need deterministic order.
     pub fn pair_iter(&self) -> impl Iterator<Item = (Handle<Node>, &Node)> {
         self.pool.pair_iter()
     }
 
+    /// Same as [`Self::pair_iter`], but also gives handle of parent of each node, so items are
+    /// (handle; parent handle; node). Parent handle of root is [`Handle::NONE`].
+    pub fn pair_iter_with_parent(
+        &self,
+    ) -> impl Iterator<Item = (Handle<Node>, Handle<Node>, &Node)> {
+        self.pool
+            .pair_iter()
+            .map(|(handle, node)| (handle, node.parent(), node))
+    }
+
+    /// Creates new iterator that gives (handle; node) pairs in depth-first order starting
+    /// from root, where a parent goes before its children and children go in order of their
+    /// appearance in children list. Unlike [`Self::pair_iter`], the order depends only on
+    /// the hierarchy, not on memory layout of nodes. Nodes that are not reachable from root
+    /// are not visited.
+    ///
+    /// # Notes
+    ///
+    /// This method allocates temporal array so it is not cheap! Should not be
+    /// used on each frame.
+    pub fn pair_iter_hierarchical(&self) -> impl Iterator<Item = (Handle<Node>, &Node)> {
+        self.render_order(self.root)
+            .into_iter()
+            .map(move |handle| (handle, &self.pool[handle]))
+    }
+
     /// Creates new iterator that iterates over internal collection giving (handle; node) pairs.
     pub fn pair_iter_mut(&mut self) -> impl Iterator<Item = (Handle<Node>, &mut Node)> {
         self.pool.pair_iter_mut()
     }
 
+    /// Returns position of a node in [draw order](Self::render_order) of the whole graph, so
+    /// a node with greater index is drawn above a node with lesser index. Returns `None` if
+    /// the node can not be reached from root.
+    pub fn draw_order_index(&self, node: Handle<Node>) -> Option<usize> {
+        let mut index = 0;
+        let mut stack = vec![self.root];
+        while let Some(handle) = stack.pop() {
+            if let Some(current) = self.pool.try_borrow(handle) {
+                if handle == node {
+                    return Some(index);
+                }
+                index += 1;
+                stack.extend(current.children().iter().rev());
+            }
+        }
+        None
+    }
+
+    /// Returns handles of nodes of a subtree starting from `from` in draw order. Draw order
+    /// is depth-first: a parent is drawn before its children, and children are drawn in order
+    /// of their appearance in children list, so last child is drawn on top of its siblings.
+    /// The 2D renderer draws sprites in this order.
+    ///
+    /// # Notes
+    ///
+    /// This method allocates temporal array on each call.
+    pub fn render_order(&self, from: Handle<Node>) -> Vec<Handle<Node>> {
+        let mut order = Vec::new();
+        let mut stack = vec![from];
+        while let Some(handle) = stack.pop() {
+            if let Some(node) = self.pool.try_borrow(handle) {
+                order.push(handle);
+                // Reverse children to pop first child first.
+                stack.extend(node.children().iter().rev());
+            }
+        }
+        order
+    }
+
+    /// Creates an iterator that yields nodes of a subtree starting from `from` in reverse
+    /// draw order (see [`Self::render_order`]) - visually topmost node is yielded first, so
+    /// first node that passed hit test is the one that user actually sees.
+    ///
+    /// # Notes
+    ///
+    /// This method allocates temporal array so it is not cheap! Should not be
+    /// used on each frame.
+    pub fn traverse_hit_test(
+        &self,
+        from: Handle<Node>,
+    ) -> impl Iterator<Item = (Handle<Node>, &Node)> {
+        self.render_order(from)
+            .into_iter()
+            .rev()
+            .map(move |handle| (handle, &self.pool[handle]))
+    }
+
     /// Extracts node from graph and reserves its handle. It is used to temporarily take
     /// ownership over node, and then put node back using given ticket. Extracted node is
     /// detached from its parent!
     pub fn take_reserve(&mut self, handle: Handle<Node>) -> (Ticket<Node>, Node) {
         self.unlink_internal(handle);
-        self.pool.take_reserve(handle)
+        let (ticket, node) = self.pool.take_reserve(handle);
+        self.id_map.remove(&node.id());
+        (ticket, node)
     }
 
     /// Puts node back by given ticket. Attaches back to root node of graph.
     pub fn put_back(&mut self, ticket: Ticket<Node>, node: Node) -> Handle<Node> {
+        let id = node.id();
         let handle = self.pool.put_back(ticket, node);
+        self.id_map.insert(id, handle);
         self.link_nodes(handle, self.root);
         handle
     }
@@ -182,48 +761,403 @@ impl Graph {
         self.pool.forget_ticket(ticket)
     }
 
-    pub fn update(&mut self, render_target_size: Vector2<f32>, _dt: f32) {
+    /// Updates the graph. `dt` is time (in seconds) passed since previous update, it is passed
+    /// to every time-dependent part of the graph (update callbacks, cameras, etc.). Update is
+    /// split into phases that are always executed in the following order:
+    ///
+    /// 1. Node phase - [update callbacks](Self::set_update_callback) of enabled nodes are
+    ///    called, so they can move nodes before global data is calculated. Disabling a node
+    ///    pauses callbacks and cameras of its whole subtree.
+    /// 2. Transform phase - global transform, visibility, etc. of every node are calculated
+    ///    (see [`Self::update_hierarchical_data`]).
+    /// 3. Constraint phase - [rotation constraints](Self::set_rotation_constraint) are applied
+    ///    using final global transforms of their targets.
+    /// 4. Late phase - cameras are updated. At this moment every other node has its final
+    ///    global transform for the frame, so a camera that follows some node never lags
+    ///    behind by a frame.
+    pub fn update(&mut self, render_target_size: Vector2<f32>, dt: f32) {
+        self.frame += 1;
+
+        self.update_callbacks(dt);
+
         self.update_hierarchical_data();
 
+        self.apply_constraints();
+
+        self.update_cameras(render_target_size, dt);
+    }
+
+    /// Sets a closure that will be called for a node on every [`Self::update`] (while the node
+    /// is [globally enabled](crate::scene2d::base::Base::global_enabled)) with mutable reference
+    /// to the node and time step. Handy for quick scripted behavior. Callbacks are runtime-only:
+    /// they are not saved and not copied together with the node. Previous callback of the node
+    /// is replaced.
+    pub fn set_update_callback(&mut self, node: Handle<Node>, callback: UpdateCallback) {
+        self.pool[node].update_callback = Some(callback);
+    }
+
+    /// Removes update callback of a node and returns it, see [`Self::set_update_callback`].
+    pub fn take_update_callback(&mut self, node: Handle<Node>) -> Option<UpdateCallback> {
+        self.pool
+            .try_borrow_mut(node)
+            .and_then(|node| node.update_callback.take())
+    }
+
+    /// Adds a closure that will be called with new global transform of a node every time
+    /// [`Self::update_hierarchical_data`] changes it. Watchers are runtime-only: they are not
+    /// saved, not copied and dropped together with the node.
+    pub fn watch_transform(&mut self, node: Handle<Node>, watcher: TransformWatcher) {
+        self.pool[node].transform_watchers.push(watcher);
+    }
+
+    /// Removes all transform watchers of a node, see [`Self::watch_transform`].
+    pub fn clear_transform_watchers(&mut self, node: Handle<Node>) {
+        if let Some(node) = self.pool.try_borrow_mut(node) {
+            node.transform_watchers.clear();
+        }
+    }
+
+    /// Node update phase, see [`Self::update`].
+    fn update_callbacks(&mut self, dt: f32) {
+        // Global data is not calculated yet at this point, so check ancestors directly to not
+        // run callbacks of a subtree that was disabled right before the update.
+        let enabled = self
+            .pool
+            .pair_iter()
+            .filter(|&(handle, node)| {
+                node.update_callback.is_some() && self.is_effectively_enabled(handle)
+            })
+            .map(|(handle, _)| handle)
+            .collect::<Vec<_>>();
+
+        for handle in enabled {
+            let node = &mut self.pool[handle];
+            // Take callback out of the node to be able to pass the node to the callback.
+            if let Some(mut callback) = node.update_callback.take() {
+                callback(node, dt);
+                // Callback could replace itself, keep the new one then.
+                if node.update_callback.is_none() {
+                    node.update_callback = Some(callback);
+                }
+            }
+        }
+    }
+
+    /// Makes a node copy world rotation of `target` node on every [`Self::update`], local
+    /// rotation of the node is adjusted so its world rotation matches rotation of the target.
+    /// [`Handle::NONE`] target removes the constraint. Constraints are evaluated once per update
+    /// in no particular order, so a target should not be constrained itself.
+    pub fn set_rotation_constraint(&mut self, node: Handle<Node>, target: Handle<Node>) {
+        self.set_rotation_constraint_with_offset(node, target, 0.0);
+    }
+
+    /// Same as [`Self::set_rotation_constraint`], but given angle (in radians) is added to
+    /// world rotation of the target.
+    pub fn set_rotation_constraint_with_offset(
+        &mut self,
+        node: Handle<Node>,
+        target: Handle<Node>,
+        offset: f32,
+    ) {
+        self.pool[node].rotation_constraint = if target.is_some() {
+            Some(RotationConstraint { target, offset })
+        } else {
+            None
+        };
+    }
+
+    /// Constraint update phase, see [`Self::update`].
+    fn apply_constraints(&mut self) {
+        fn world_rotation(m: &Matrix4<f32>) -> f32 {
+            m[(1, 0)].atan2(m[(0, 0)])
+        }
+
+        let constrained = self
+            .pool
+            .pair_iter()
+            .filter_map(|(handle, node)| node.rotation_constraint.map(|c| (handle, c)))
+            .collect::<Vec<_>>();
+
+        let mut rotated = false;
+        for (handle, constraint) in constrained {
+            if !self.pool.is_valid_handle(constraint.target) {
+                continue;
+            }
+
+            let target_rotation = world_rotation(&self.global_transform(constraint.target));
+            let parent = self.pool[handle].parent();
+            let parent_rotation = if self.pool.is_valid_handle(parent) {
+                world_rotation(&self.global_transform(parent))
+            } else {
+                0.0
+            };
+            let transform = self.pool[handle].local_transform();
+            let rotation =
+                transform.clamp_rotation(target_rotation + constraint.offset - parent_rotation);
+            if (transform.rotation() - rotation).abs() > f32::EPSILON {
+                self.pool[handle]
+                    .local_transform_mut()
+                    .set_rotation(rotation);
+                rotated = true;
+            }
+        }
+
+        // Let descendants of constrained nodes follow them. The second pass must not lose
+        // nodes changed by the main pass, so its changes are appended to them.
+        if rotated {
+            let mut changed = std::mem::take(&mut self.changed);
+            self.update_hierarchical_data();
+            let known = changed.iter().copied().collect::<HashSet<_>>();
+            changed.extend(
+                self.changed
+                    .iter()
+                    .copied()
+                    .filter(|handle| !known.contains(handle)),
+            );
+            self.changed = changed;
+        }
+    }
+
+    /// Late update phase, see [`Self::update`].
+    fn update_cameras(&mut self, render_target_size: Vector2<f32>, dt: f32) {
         for node in self.pool.iter_mut() {
             if let Node::Camera(camera) = node {
-                camera.update(render_target_size);
+                if camera.global_enabled() {
+                    camera.update(render_target_size, dt);
+                }
             }
         }
     }
 
+    /// Marks a node (and implicitly its subtree) as needing recalculation of global transform
+    /// and other global data on next [`Self::update_hierarchical_data`] call. Setters of a node
+    /// and linking methods of the graph do this automatically, this method is an escape hatch
+    /// for code that changes state of a node bypassing them. Does nothing for invalid handle.
+    pub fn invalidate(&mut self, node: Handle<Node>) {
+        if let Some(node) = self.pool.try_borrow(node) {
+            node.dirty.set(true);
+        }
+    }
+
     /// Checks whether given node handle is valid or not.
     pub fn is_valid_handle(&self, node_handle: Handle<Node>) -> bool {
         self.pool.is_valid_handle(node_handle)
     }
 
     /// Calculates local and global transform, global visibility for each node in graph.
+    /// Root node is treated as any other node: its local transform and visibility are applied
+    /// to the whole scene, so a scene-wide offset or scale can be set on the root. Root has
+    /// identity transform by default.
     /// Normally you not need to call this method directly, it will be called automatically
     /// on each frame. However there is one use case - when you setup complex hierarchy and
     /// need to know global transform of nodes before entering update loop, then you can call
     /// this method.
+    ///
+    /// Does nothing inside of [`Self::batch_edit`], the batch will do the update when it ends.
+    /// Global data is recalculated only for subtrees of dirty nodes (see
+    /// [`Self::mark_dirty_subtree`]), the rest of the graph is just walked through. Nodes that
+    /// were changed (directly or by their ancestors) since previous update are recorded, see
+    /// [`Self::changed_since_last_update`].
+    ///
+    /// Children of nodes with layout are positioned (see [`Self::perform_layout`]) before
+    /// global data is calculated.
     pub fn update_hierarchical_data(&mut self) {
-        fn update_recursively(graph: &Graph, node_handle: Handle<Node>) {
+        self.update_hierarchical_data_with(|_, _| {});
+    }
+
+    /// Does the same as [`Self::update_hierarchical_data`], but also calls `f` for every node
+    /// reachable from root right after global data of the node is final, in depth-first order
+    /// (parents before children). So systems that react to global transforms can do their job
+    /// in the same traversal. Unchanged nodes are passed too, see
+    /// [`Self::changed_since_last_update`] to tell them apart. `f` is not called inside of
+    /// [`Self::batch_edit`], since there is no update.
+    pub fn update_hierarchical_data_with<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Handle<Node>, &Node),
+    {
+        if self.batch_depth > 0 {
+            return;
+        }
+
+        self.perform_layout();
+
+        fn update_recursively(
+            graph: &Graph,
+            node_handle: Handle<Node>,
+            parent_changed: bool,
+            parent_static: bool,
+            changed: &mut Vec<Handle<Node>>,
+            moved: &mut Vec<Handle<Node>>,
+            f: &mut dyn FnMut(Handle<Node>, &Node),
+        ) {
             let node = &graph.pool[node_handle];
+            let node_changed = parent_changed || node.dirty.get();
+            let node_static = parent_static || node.is_static();
+            if node_changed {
+                let (parent_global_transform, parent_visibility, parent_enabled) =
+                    if let Some(parent) = graph.pool.try_borrow(node.parent()) {
+                        (
+                            parent.global_transform(),
+                            parent.global_visibility(),
+                            parent.global_enabled(),
+                        )
+                    } else {
+                        (Matrix4::identity(), true, true)
+                    };
 
-            let (parent_global_transform, parent_visibility) =
-                if let Some(parent) = graph.pool.try_borrow(node.parent()) {
-                    (parent.global_transform(), parent.global_visibility())
-                } else {
-                    (Matrix4::identity(), true)
-                };
+                if !node.frozen() {
+                    let global_transform =
+                        parent_global_transform * node.local_transform().matrix();
+                    if node.global_transform.replace(global_transform) != global_transform {
+                        moved.push(node_handle);
+                        if cfg!(debug_assertions) && node_static && node.static_baked.get() {
+                            Log::writeln(
+                                MessageKind::Warning,
+                                format!(
+                                    "Node {} ({:?}) of a static subtree has moved, static \
+                                     batch won't be rebuilt!",
+                                    node.name(),
+                                    node_handle
+                                ),
+                            );
+                        }
+                    }
+                }
+                node.global_visibility
+                    .set(parent_visibility && node.visibility());
+                node.global_enabled.set(parent_enabled && node.enabled());
+                node.dirty.set(false);
 
-            node.global_transform
-                .set(parent_global_transform * node.local_transform().matrix());
-            node.global_visibility
-                .set(parent_visibility && node.visibility());
+                if graph.is_change_reportable(node) {
+                    changed.push(node_handle);
+                }
+            }
+            node.static_baked.set(node_static);
+            f(node_handle, node);
 
             for &child in node.children() {
-                update_recursively(graph, child);
+                update_recursively(graph, child, node_changed, node_static, changed, moved, f);
+            }
+        }
+
+        let mut changed = std::mem::take(&mut self.changed);
+        changed.clear();
+        let mut moved = Vec::new();
+        update_recursively(
+            self,
+            self.root,
+            false,
+            false,
+            &mut changed,
+            &mut moved,
+            &mut f,
+        );
+        self.changed = changed;
+        if !moved.is_empty() {
+            self.transform_epoch += 1;
+        }
+
+        for handle in moved {
+            let node = &mut self.pool[handle];
+            let global_transform = node.global_transform();
+            for watcher in node.transform_watchers.iter_mut() {
+                watcher(&global_transform);
+            }
+        }
+
+        self.structure_changed = false;
+    }
+
+    /// Returns a counter that is incremented every time [`Self::update_hierarchical_data`]
+    /// changes global transform of at least one node. External caches can store the value and
+    /// compare it later to cheaply find out whether anything moved.
+    pub fn transform_epoch(&self) -> u64 {
+        self.transform_epoch
+    }
+
+    /// Returns handles of nodes which global data was recalculated by last
+    /// [`Self::update_hierarchical_data`] call because the nodes or their ancestors were
+    /// changed (moved, relinked, added, etc.). Handles are in depth-first order and could
+    /// be invalid if nodes were removed after the update. After [`Self::update`], nodes that
+    /// were changed only by [rotation constraints](Self::set_rotation_constraint) follow the
+    /// rest.
+    pub fn changed_since_last_update(&self) -> &[Handle<Node>] {
+        &self.changed
+    }
+
+    /// Same as [`Self::changed_since_last_update`], but yields only nodes of given kind.
+    pub fn changed_since_last_update_of_kind(
+        &self,
+        kind: NodeKind,
+    ) -> impl Iterator<Item = Handle<Node>> + '_ {
+        self.changed.iter().copied().filter(move |&handle| {
+            self.pool
+                .try_borrow(handle)
+                .map_or(false, |node| node.kind() == kind)
+        })
+    }
+
+    /// Returns true if nodes were added, removed, relinked or changed after last
+    /// [`Self::update_hierarchical_data`] call, so cached global data of some nodes may be
+    /// stale. Becomes false right after update pass.
+    pub fn is_hierarchy_dirty(&self) -> bool {
+        self.structure_changed || self.pool.iter().any(|node| node.dirty.get())
+    }
+
+    /// Runs given closure as a single batch of edits. Global data of nodes is not recalculated
+    /// inside of the batch, instead [`Self::update_hierarchical_data`] is called exactly once
+    /// when the batch ends. Nested batches are coalesced into the outermost one. Handy for
+    /// importers and procedural generators that change lots of nodes at once.
+    pub fn batch_edit<F, R>(&mut self, func: F) -> R
+    where
+        F: FnOnce(&mut Graph) -> R,
+    {
+        // Restores depth even if `func` panics, so the graph won't stay in batch mode forever.
+        struct BatchGuard<'a>(&'a mut Graph);
+
+        impl Drop for BatchGuard<'_> {
+            fn drop(&mut self) {
+                self.0.batch_depth -= 1;
             }
         }
 
-        update_recursively(self, self.root);
+        self.batch_depth += 1;
+        let mut guard = BatchGuard(&mut *self);
+        let result = func(&mut *guard.0);
+        drop(guard);
+        if self.batch_depth == 0 {
+            self.update_hierarchical_data();
+        }
+        result
+    }
+
+    /// Returns true if the graph is inside of [`Self::batch_edit`].
+    pub fn is_batch_editing(&self) -> bool {
+        self.batch_depth > 0
+    }
+
+    /// Resets position, rotation and scale of a node to identity (zero position and rotation,
+    /// unit scale) and returns previous transform, so it can be restored later. Pivot and
+    /// rotation limits are kept, zero rotation is clamped to the limits. Returns `None` and
+    /// does nothing for invalid handle.
+    pub fn reset_transform(&mut self, node: Handle<Node>) -> Option<Transform> {
+        self.pool.try_borrow_mut(node).map(|node| {
+            let transform = node.local_transform_mut();
+            let previous = transform.clone();
+            transform
+                .set_position(Vector2::default())
+                .set_rotation(0.0)
+                .set_scale(Vector2::new(1.0, 1.0));
+            previous
+        })
+    }
+
+    /// Returns local transformation matrix of a node (without influence of its parents), or
+    /// identity matrix for invalid handle.
+    pub fn local_transform_matrix(&self, node: Handle<Node>) -> Matrix4<f32> {
+        self.pool
+            .try_borrow(node)
+            .map_or_else(Matrix4::identity, |node| node.local_transform().matrix())
     }
 
     /// Returns local transformation matrix of a node without scale.
@@ -252,6 +1186,7 @@ impl Graph {
         TransformBuilder::new()
             .with_position(transform.position())
             .with_rotation(transform.rotation())
+            .with_pivot(transform.pivot())
             .build()
             .matrix()
     }
@@ -322,18 +1257,2928 @@ impl Graph {
         let m = self.global_scale_matrix(node);
         Vector2::new(m[0], m[5])
     }
-}
 
-impl Index<Handle<Node>> for Graph {
-    type Output = Node;
+    /// Decomposes cached global transform of a node into world position, rotation angle (in
+    /// radians) and scale at once. Unlike [`Self::global_scale`], the scale is correct for
+    /// nodes with rotated ancestors. Skew (caused by non-uniformly scaled and rotated
+    /// ancestors) can not be represented, so only closest rotation and scale are returned in
+    /// this case. Mirroring goes to Y scale.
+    pub fn global_transform_decomposed(
+        &self,
+        node: Handle<Node>,
+    ) -> (Vector2<f32>, f32, Vector2<f32>) {
+        decompose_transform(&self[node].global_transform())
+    }
 
-    fn index(&self, index: Handle<Node>) -> &Self::Output {
-        &self.pool[index]
+    /// Returns true if the node or any of its ancestors was changed after last
+    /// [`Self::update_hierarchical_data`] call, so cached global data of the node is stale.
+    fn is_stale(&self, node: Handle<Node>) -> bool {
+        let mut handle = node;
+        while let Some(current) = self.pool.try_borrow(handle) {
+            if current.dirty.get() {
+                return true;
+            }
+            handle = current.parent();
+        }
+        false
     }
-}
 
-impl IndexMut<Handle<Node>> for Graph {
-    fn index_mut(&mut self, index: Handle<Node>) -> &mut Self::Output {
-        &mut self.pool[index]
+    /// Returns global transform of a node. If the node or any of its ancestors was changed
+    /// after last [`Self::update_hierarchical_data`] call, the transform is recalculated on
+    /// demand, otherwise cached value is returned. Returns identity matrix for invalid handle.
+    pub fn global_transform(&self, node: Handle<Node>) -> Matrix4<f32> {
+        debug_assert!(
+            self.is_valid_handle(node),
+            "Attempt to get global transform of a node using invalid handle!"
+        );
+        if self.is_stale(node) {
+            self.compute_global_transform(node)
+        } else {
+            self.pool
+                .try_borrow(node)
+                .map_or_else(Matrix4::identity, |node| node.global_transform())
+        }
+    }
+
+    /// Calculates global transform of a node by walking up its chain of ancestors. Unlike
+    /// cached global transform of a node, it is correct even if the hierarchy was changed
+    /// after last [`Self::update_hierarchical_data`] call. Returns identity matrix for
+    /// invalid handle.
+    pub fn compute_global_transform(&self, node: Handle<Node>) -> Matrix4<f32> {
+        let mut transform = Matrix4::identity();
+        let mut handle = node;
+        while let Some(current) = self.pool.try_borrow(handle) {
+            transform = current.local_transform().matrix() * transform;
+            handle = current.parent();
+        }
+        transform
+    }
+
+    /// Calculates global position of a node by walking up its chain of ancestors. See
+    /// [`Self::compute_global_transform`] for more info.
+    pub fn compute_global_position(&self, node: Handle<Node>) -> Vector2<f32> {
+        let m = self.compute_global_transform(node);
+        Vector2::new(m[12], m[13])
+    }
+
+    /// Calculates visibility of a node by walking up its chain of ancestors, stops at first
+    /// invisible one. Unlike [global visibility](crate::scene2d::base::Base::global_visibility)
+    /// of a node, it is correct even if visibility was changed after last
+    /// [`Self::update_hierarchical_data`] call. Returns false for invalid handle.
+    pub fn is_effectively_visible(&self, node: Handle<Node>) -> bool {
+        let mut handle = node;
+        let mut visited = false;
+        while let Some(current) = self.pool.try_borrow(handle) {
+            if !current.visibility() {
+                return false;
+            }
+            visited = true;
+            handle = current.parent();
+        }
+        visited
+    }
+
+    /// Calculates whether a node is enabled by walking up its chain of ancestors, stops at first
+    /// disabled one. Unlike [global enabled](crate::scene2d::base::Base::global_enabled) flag
+    /// of a node, it is correct even if the flag was changed after last
+    /// [`Self::update_hierarchical_data`] call. Returns false for invalid handle.
+    pub fn is_effectively_enabled(&self, node: Handle<Node>) -> bool {
+        let mut handle = node;
+        let mut visited = false;
+        while let Some(current) = self.pool.try_borrow(handle) {
+            if !current.enabled() {
+                return false;
+            }
+            visited = true;
+            handle = current.parent();
+        }
+        visited
+    }
+
+    /// Moves a node to given position in world coordinates. Local position of the node is
+    /// calculated using current global transform of its parent, pivot of the node is taken
+    /// into account so origin of the node ends up at given position.
+    pub fn set_global_position(&mut self, node: Handle<Node>, position: Vector2<f32>) {
+        let parent_inv_transform = self
+            .compute_global_transform(self.pool[node].parent())
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity);
+        let local_position = parent_inv_transform
+            .transform_point(&Point3::new(position.x, position.y, 0.0))
+            .coords
+            .xy();
+        let transform = self.pool[node].local_transform_mut();
+        let pivot_offset = transform.set_position(Vector2::default()).matrix();
+        transform.set_position(local_position - Vector2::new(pivot_offset[12], pivot_offset[13]));
+    }
+
+    /// Moves a node by given delta in its own coordinate system, the delta is rotated by local
+    /// rotation of the node (scale is ignored), so positive X means "forward" for a node that
+    /// faces along X axis. The node is marked as dirty.
+    pub fn translate_local(&mut self, node: Handle<Node>, delta: Vector2<f32>) {
+        let transform = self.pool[node].local_transform_mut();
+        let delta = UnitComplex::new(transform.rotation()) * delta;
+        transform.offset(delta);
+    }
+
+    /// Moves a node by given delta in world coordinates. The delta is converted to the
+    /// coordinate system of parent of the node using current global transform of the parent,
+    /// so the node moves by exactly given delta in the world. The node is marked as dirty.
+    pub fn translate_world(&mut self, node: Handle<Node>, delta: Vector2<f32>) {
+        let parent_inv_transform = self
+            .compute_global_transform(self.pool[node].parent())
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity);
+        let local_delta = parent_inv_transform
+            .transform_vector(&Vector3::new(delta.x, delta.y, 0.0))
+            .xy();
+        self.pool[node].local_transform_mut().offset(local_delta);
+    }
+
+    /// Clamps world position of a node so it will be inside of given bounds. Clamping is
+    /// done in world space, then local position of the node is adjusted taking transform
+    /// of its parent into account. Does nothing if the node is already inside of the bounds.
+    pub fn clamp_position(&mut self, node: Handle<Node>, bounds: Rect<f32>) {
+        let position = self.compute_global_position(node);
+        let clamped = Vector2::new(
+            position
+                .x
+                .max(bounds.position.x)
+                .min(bounds.position.x + bounds.size.x),
+            position
+                .y
+                .max(bounds.position.y)
+                .min(bounds.position.y + bounds.size.y),
+        );
+        if clamped != position {
+            self.set_global_position(node, clamped);
+        }
+    }
+
+    /// Rounds world position of a node to the nearest multiple of `grid`, then adjusts local
+    /// position of the node taking transform of its parent into account (see
+    /// [`Self::set_global_position`]). Zero (or negative) component of the grid leaves
+    /// respective axis unsnapped. The node is marked as dirty.
+    pub fn snap_to_grid(&mut self, node: Handle<Node>, grid: Vector2<f32>) {
+        let snap = |value: f32, step: f32| {
+            if step > 0.0 {
+                (value / step).round() * step
+            } else {
+                value
+            }
+        };
+        let position = self.compute_global_position(node);
+        let snapped = Vector2::new(snap(position.x, grid.x), snap(position.y, grid.y));
+        self.set_global_position(node, snapped);
+    }
+
+    /// Moves all alive nodes to the beginning of internal storage, removing vacant entries
+    /// between them. Every handle to a node becomes invalid after this, returned old-to-new
+    /// map should be used to fix them (see [`RemapHandles`]). Links between nodes, root,
+    /// active camera and id map are fixed automatically.
+    pub fn compact(&mut self) -> HashMap<Handle<Node>, Handle<Node>> {
+        let handles = self
+            .pool
+            .pair_iter()
+            .map(|(handle, _)| handle)
+            .collect::<Vec<_>>();
+
+        let mut pool = Pool::with_capacity(handles.len());
+        let mut map = HashMap::with_capacity(handles.len());
+        for handle in handles {
+            let node = self.pool.free(handle);
+            map.insert(handle, pool.spawn(node));
+        }
+        self.pool = pool;
+
+        self.remap(&map);
+
+        map
+    }
+
+    /// Does the same as [`Self::compact`], but also fixes handles in every given object.
+    pub fn compact_with(
+        &mut self,
+        others: &mut [&mut dyn RemapHandles],
+    ) -> HashMap<Handle<Node>, Handle<Node>> {
+        let map = self.compact();
+        for other in others.iter_mut() {
+            other.remap(&map);
+        }
+        map
+    }
+
+    /// Returns oriented bounding box of a node - four corners of local bounds of the node
+    /// transformed to world space by its global transform. Corners are ordered as left-top,
+    /// right-top, right-bottom, left-bottom corners of local bounds, so winding order is the
+    /// same for every node (unless the node is mirrored). All corners are equal to global
+    /// position of the node if it has no bounds.
+    ///
+    /// Bounds of [screen-space](Self::is_in_screen_space) nodes are converted to world
+    /// coordinates using view of active camera.
+    pub fn world_obb(&self, node: Handle<Node>) -> [Vector2<f32>; 4] {
+        if self.is_in_screen_space(node) {
+            self.transformed_obb(node, self.screen_to_world() * self.global_transform(node))
+        } else {
+            self.transformed_obb(node, self.global_transform(node))
+        }
+    }
+
+    /// Returns corners of local bounds of a node transformed by given matrix, see
+    /// [`Self::world_obb`].
+    fn transformed_obb(&self, node: Handle<Node>, matrix: Matrix4<f32>) -> [Vector2<f32>; 4] {
+        let transform = |p: Vector2<f32>| {
+            matrix
+                .transform_point(&Point3::new(p.x, p.y, 0.0))
+                .coords
+                .xy()
+        };
+        let local_bounds = self
+            .pool
+            .try_borrow(node)
+            .and_then(|node| node.local_bounds())
+            .unwrap_or_default();
+        [
+            transform(local_bounds.left_top_corner()),
+            transform(local_bounds.right_top_corner()),
+            transform(local_bounds.right_bottom_corner()),
+            transform(local_bounds.left_bottom_corner()),
+        ]
+    }
+
+    /// Returns axis-aligned bounding box of a node in world coordinates, it encloses
+    /// [oriented bounding box](Self::world_obb) of the node.
+    pub fn world_aabb(&self, node: Handle<Node>) -> Rect<f32> {
+        bounding_rect(&self.world_obb(node))
+    }
+
+    /// Checks whether world bounds of two nodes intersect. The test is precise for rotated,
+    /// scaled and skewed nodes - it uses separating axis theorem on
+    /// [oriented bounding boxes](Self::world_obb) of the nodes, not just their axis-aligned
+    /// bounds. Nodes without bounds are treated as points, touching boxes are considered
+    /// overlapping.
+    pub fn overlaps(&self, a: Handle<Node>, b: Handle<Node>) -> bool {
+        fn project(obb: &[Vector2<f32>; 4], axis: &Vector2<f32>) -> (f32, f32) {
+            obb.iter()
+                .fold((f32::MAX, -f32::MAX), |(min, max), corner| {
+                    let projection = corner.dot(axis);
+                    (min.min(projection), max.max(projection))
+                })
+        }
+
+        let a = self.world_obb(a);
+        let b = self.world_obb(b);
+
+        // World axes are checked too, they're enough for degenerate boxes (points and lines).
+        let mut axes = vec![Vector2::x(), Vector2::y()];
+        for obb in [&a, &b].iter() {
+            // Box is a parallelogram, so normals of two adjacent edges are enough.
+            for i in 0..2 {
+                let edge = obb[i + 1] - obb[i];
+                let normal = Vector2::new(-edge.y, edge.x);
+                if normal.norm_squared() > f32::EPSILON {
+                    axes.push(normal);
+                }
+            }
+        }
+
+        axes.iter().all(|axis| {
+            let (a_min, a_max) = project(&a, axis);
+            let (b_min, b_max) = project(&b, axis);
+            a_max >= b_min && b_max >= a_min
+        })
+    }
+
+    /// Returns amount of direct children of a node (descendants are not counted). Returns 0
+    /// for invalid handle.
+    pub fn child_count(&self, node: Handle<Node>) -> usize {
+        self.pool
+            .try_borrow(node)
+            .map_or(0, |node| node.children().len())
+    }
+
+    /// Returns amount of nodes in a subtree starting from `root` (including `root` itself) that
+    /// satisfy given predicate. Returns 0 for invalid handle.
+    pub fn count_subtree<P>(&self, root: Handle<Node>, predicate: P) -> usize
+    where
+        P: Fn(&Node) -> bool,
+    {
+        let mut count = 0;
+        let mut stack = vec![root];
+        while let Some(handle) = stack.pop() {
+            if let Some(node) = self.pool.try_borrow(handle) {
+                if predicate(node) {
+                    count += 1;
+                }
+                stack.extend_from_slice(node.children());
+            }
+        }
+        count
+    }
+
+    /// Returns handle of parent of a node, or [`Handle::NONE`] if the node has no parent or
+    /// the handle is invalid.
+    pub fn parent(&self, node: Handle<Node>) -> Handle<Node> {
+        self.pool
+            .try_borrow(node)
+            .map_or(Handle::NONE, |node| node.parent())
+    }
+
+    /// Returns the ancestor of a node that is a direct child of root, i.e. the top of the
+    /// object the node belongs to. Returns the node itself if it is a direct child of root.
+    /// Returns [`Handle::NONE`] for root, invalid handle or a node that is not attached to root.
+    pub fn root_of(&self, node: Handle<Node>) -> Handle<Node> {
+        if node == self.root {
+            return Handle::NONE;
+        }
+        let mut handle = node;
+        while let Some(current) = self.pool.try_borrow(handle) {
+            if current.parent() == self.root {
+                return handle;
+            }
+            handle = current.parent();
+        }
+        Handle::NONE
+    }
+
+    /// Returns true if both nodes belong to the same top-level object (see [`Self::root_of`]),
+    /// which is also the case when one node is an ancestor of the other one (but not root).
+    /// Returns false if any of the nodes is root, invalid or not attached to root.
+    pub fn same_object(&self, a: Handle<Node>, b: Handle<Node>) -> bool {
+        let object = self.root_of(a);
+        object.is_some() && object == self.root_of(b)
+    }
+
+    /// Returns handle of first child of a node, or [`Handle::NONE`] if there is no such.
+    pub fn first_child(&self, node: Handle<Node>) -> Handle<Node> {
+        self.pool
+            .try_borrow(node)
+            .and_then(|node| node.children().first().copied())
+            .unwrap_or(Handle::NONE)
+    }
+
+    /// Returns handle of last child of a node, or [`Handle::NONE`] if there is no such.
+    pub fn last_child(&self, node: Handle<Node>) -> Handle<Node> {
+        self.pool
+            .try_borrow(node)
+            .and_then(|node| node.children().last().copied())
+            .unwrap_or(Handle::NONE)
+    }
+
+    /// Returns handle of a sibling that goes right after the node in children list of its
+    /// parent, or [`Handle::NONE`] if the node is the last one.
+    pub fn next_sibling(&self, node: Handle<Node>) -> Handle<Node> {
+        self.sibling_at_offset(node, 1)
+    }
+
+    /// Returns handle of a sibling that goes right before the node in children list of its
+    /// parent, or [`Handle::NONE`] if the node is the first one.
+    pub fn prev_sibling(&self, node: Handle<Node>) -> Handle<Node> {
+        self.sibling_at_offset(node, -1)
+    }
+
+    /// Returns handles of other children of parent of a node in their order, the node itself is
+    /// excluded. Returns empty list for root, a node without parent or invalid handle.
+    pub fn siblings(&self, node: Handle<Node>) -> Vec<Handle<Node>> {
+        self.pool
+            .try_borrow(self.parent(node))
+            .map_or_else(Vec::new, |parent| {
+                parent
+                    .children()
+                    .iter()
+                    .copied()
+                    .filter(|&sibling| sibling != node)
+                    .collect()
+            })
+    }
+
+    fn sibling_at_offset(&self, node: Handle<Node>, offset: isize) -> Handle<Node> {
+        self.pool
+            .try_borrow(self.parent(node))
+            .and_then(|parent| {
+                let siblings = parent.children();
+                let index = siblings.iter().position(|&h| h == node)? as isize + offset;
+                if index >= 0 {
+                    siblings.get(index as usize).copied()
+                } else {
+                    None
+                }
+            })
+            .unwrap_or(Handle::NONE)
+    }
+
+    /// Compares the graph with `other` matching nodes by their stable ids and returns set of
+    /// changes that turns this graph into `other`. Root nodes of both graphs are always matched
+    /// with each other, only local transform, name and parent of matched nodes are compared.
+    pub fn diff(&self, other: &Graph) -> GraphDiff {
+        let mut diff = GraphDiff::default();
+
+        for (handle, node) in other.pair_iter_hierarchical() {
+            if handle == other.root {
+                continue;
+            }
+
+            let parent = other.parent_id(handle);
+            let existing = self.node_by_id(node.id());
+            if existing.is_some() && existing != self.root {
+                let existing_node = &self.pool[existing];
+                let mut changed = ChangedNode {
+                    id: node.id(),
+                    ..Default::default()
+                };
+                if existing_node.local_transform() != node.local_transform() {
+                    changed.local_transform = Some(node.local_transform().clone());
+                }
+                if existing_node.name() != node.name() {
+                    changed.name = Some(node.name().to_owned());
+                }
+                if self.parent_id(existing) != parent {
+                    changed.parent = Some(parent);
+                }
+                diff.push_changed(changed);
+            } else {
+                let mut copy = node.raw_copy();
+                copy.id = node.id();
+                diff.added.push(AddedNode {
+                    id: node.id(),
+                    parent,
+                    node: copy,
+                });
+            }
+        }
+
+        for (handle, node) in self.pair_iter_hierarchical() {
+            if handle != self.root && other.node_by_id(node.id()).is_none() {
+                diff.removed.push(node.id());
+            }
+        }
+
+        diff
+    }
+
+    /// Returns stable id of parent of a node, nil if the node is attached to root or has no
+    /// parent.
+    fn parent_id(&self, node: Handle<Node>) -> Uuid {
+        let parent = self.pool[node].parent();
+        match self.pool.try_borrow(parent) {
+            Some(parent_node) if parent != self.root => parent_node.id(),
+            _ => Uuid::nil(),
+        }
+    }
+
+    /// Applies set of changes produced by [`Self::diff`] to the graph. Nodes are found by their
+    /// stable ids, so nodes that are not mentioned in the diff keep their state untouched.
+    /// Removed nodes are freed together with their subtrees, added nodes keep ids from the diff.
+    /// Changes that refer to missing nodes are ignored, missing parents are replaced with root.
+    pub fn apply_diff(&mut self, diff: GraphDiff) {
+        for id in diff.removed {
+            let handle = self.node_by_id(id);
+            if handle.is_some() && handle != self.root {
+                self.remove_node(handle);
+            }
+        }
+
+        for added in diff.added {
+            if self.node_by_id(added.id).is_some() {
+                continue;
+            }
+            let mut node = added.node;
+            node.id = added.id;
+            node.children.clear();
+            let handle = self.add_node(node);
+            let parent = self.handle_by_diff_id(added.parent);
+            if parent != self.root {
+                self.link_nodes(handle, parent);
+            }
+        }
+
+        for changed in diff.changed {
+            let handle = self.node_by_id(changed.id);
+            if handle.is_none() || handle == self.root {
+                continue;
+            }
+            let node = &mut self.pool[handle];
+            if let Some(transform) = changed.local_transform {
+                *node.local_transform_mut() = transform;
+            }
+            if let Some(name) = changed.name {
+                node.set_name(name);
+            }
+            if let Some(parent) = changed.parent {
+                let parent = self.handle_by_diff_id(parent);
+                // Never make a cycle, diff could be computed against a different graph.
+                if !self.is_descendant_of(parent, handle) {
+                    self.link_nodes(handle, parent);
+                }
+            }
+        }
+    }
+
+    fn handle_by_diff_id(&self, id: Uuid) -> Handle<Node> {
+        let handle = self.node_by_id(id);
+        if handle.is_some() {
+            handle
+        } else {
+            self.root
+        }
+    }
+
+    /// Returns true if `node` is `ancestor` or lies somewhere in its subtree.
+    fn is_descendant_of(&self, node: Handle<Node>, ancestor: Handle<Node>) -> bool {
+        let mut handle = node;
+        while let Some(current) = self.pool.try_borrow(handle) {
+            if handle == ancestor {
+                return true;
+            }
+            handle = current.parent();
+        }
+        false
+    }
+
+    /// Checks internal consistency of the graph: root must be valid, every node must be
+    /// reachable from root, parent and children links must agree with each other and every
+    /// node must be findable by its stable id.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if !self.pool.is_valid_handle(self.root) {
+            return Err(ValidationError::InvalidRoot);
+        }
+
+        for (handle, node) in self.pool.pair_iter() {
+            if handle != self.root {
+                match self.pool.try_borrow(node.parent()) {
+                    Some(parent) => {
+                        if !parent.children().contains(&handle) {
+                            return Err(ValidationError::ParentChildMismatch(handle));
+                        }
+                    }
+                    None => return Err(ValidationError::DanglingParent(handle)),
+                }
+            }
+            for &child in node.children() {
+                if self.pool.try_borrow(child).map(|c| c.parent()) != Some(handle) {
+                    return Err(ValidationError::ParentChildMismatch(child));
+                }
+            }
+            if self.node_by_id(node.id()) != handle {
+                return Err(ValidationError::StaleId(handle));
+            }
+        }
+
+        let reachable = self.render_order(self.root);
+        if reachable.len() != self.pool.alive_count() {
+            if let Some((handle, _)) = self
+                .pool
+                .pair_iter()
+                .find(|(handle, _)| !reachable.contains(handle))
+            {
+                return Err(ValidationError::Unreachable(handle));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns global transform of a node as 3D transform, so tools that work with both 2D
+    /// and 3D scenes can handle it the same way. 2D transforms are already stored in 4x4
+    /// matrices that map XY plane onto itself with z = 0, so this is the same as
+    /// [`Self::global_transform`].
+    pub fn to_3d_transform(&self, node: Handle<Node>) -> Matrix4<f32> {
+        self.global_transform(node)
+    }
+
+    /// Returns true if global transform of a node flips orientation (it has negative
+    /// determinant of its 2x2 linear part), which happens when a node or one of its ancestors
+    /// has negative scale along odd number of axes. Winding of geometry of such node is
+    /// reversed.
+    pub fn is_mirrored(&self, node: Handle<Node>) -> bool {
+        let m = self.global_transform(node);
+        m[(0, 0)] * m[(1, 1)] - m[(0, 1)] * m[(1, 0)] < 0.0
+    }
+
+    /// Sets name of a node making sure that none of its siblings has the same name: `base` is
+    /// used if it is free, otherwise first free of `base_1`, `base_2`, ... is used. Returns
+    /// final name of the node. Only siblings are checked, use [`Base::set_name`] directly if
+    /// uniqueness is not needed.
+    ///
+    /// [`Base::set_name`]: crate::scene2d::base::Base::set_name
+    pub fn set_unique_name(&mut self, node: Handle<Node>, base: &str) -> String {
+        let parent = self.pool[node].parent();
+        let is_taken = |name: &str| {
+            self.pool.try_borrow(parent).map_or(false, |parent| {
+                parent
+                    .children()
+                    .iter()
+                    .any(|&sibling| sibling != node && self.pool[sibling].name() == name)
+            })
+        };
+
+        let mut name = base.to_owned();
+        let mut index = 1;
+        while is_taken(&name) {
+            name = format!("{}_{}", base, index);
+            index += 1;
+        }
+
+        self.pool[node].set_name(&name);
+        name
+    }
+
+    /// Returns direct child of `parent` with given name, if there is no such child, then new
+    /// base node with the name is created and linked to `parent`. Only direct children are
+    /// checked, the rest of subtree is ignored.
+    pub fn child_entry(&mut self, parent: Handle<Node>, name: &str) -> Handle<Node> {
+        if let Some(&child) = self.pool[parent]
+            .children()
+            .iter()
+            .find(|&&child| self.pool[child].name() == name)
+        {
+            return child;
+        }
+
+        let child = BaseBuilder::new().with_name(name.to_owned()).build(self);
+        self.link_nodes(child, parent);
+        child
+    }
+
+    /// Positions children of every node that has [`Layout`] other than [`Layout::None`]: the
+    /// children are placed one after another in order of children list, so their local bounds
+    /// (transformed by rotation and scale of a child) do not overlap and separated by spacing
+    /// of the layout. Only one coordinate of a child is changed, children without bounds are
+    /// treated as points. Nodes without layout keep positions of their children untouched.
+    ///
+    /// Normally there is no need to call this method directly, it is called automatically by
+    /// [`Self::update_hierarchical_data`].
+    pub fn perform_layout(&mut self) {
+        let containers = self
+            .pool
+            .pair_iter()
+            .filter(|(_, node)| node.layout() != Layout::None && !node.children().is_empty())
+            .map(|(handle, _)| handle)
+            .collect::<Vec<_>>();
+
+        for container in containers {
+            let layout = self.pool[container].layout();
+            let children = self.pool[container].children().to_vec();
+            let mut cursor = 0.0;
+            for child in children {
+                let child = &mut self.pool[child];
+                let transform = child.local_transform();
+                let mut linear = transform.clone();
+                let linear = linear.set_position(Vector2::new(0.0, 0.0)).matrix();
+                let bounds = child.local_bounds().map_or_else(Rect::default, |bounds| {
+                    bounding_rect(
+                        &[
+                            bounds.left_top_corner(),
+                            bounds.right_top_corner(),
+                            bounds.right_bottom_corner(),
+                            bounds.left_bottom_corner(),
+                        ]
+                        .iter()
+                        .map(|p| {
+                            linear
+                                .transform_point(&Point3::new(p.x, p.y, 0.0))
+                                .coords
+                                .xy()
+                        })
+                        .collect::<Vec<_>>(),
+                    )
+                });
+
+                let mut position = transform.position();
+                match layout {
+                    Layout::None => (),
+                    Layout::Horizontal { spacing } => {
+                        position.x = cursor - bounds.position.x;
+                        cursor += bounds.size.x + spacing;
+                    }
+                    Layout::Vertical { spacing } => {
+                        position.y = cursor - bounds.position.y;
+                        cursor += bounds.size.y + spacing;
+                    }
+                }
+
+                if position != transform.position() {
+                    child.local_transform_mut().set_position(position);
+                }
+            }
+        }
+    }
+
+    /// Returns true if a node or any of its ancestors is marked as screen-space one, global
+    /// transform of such node is relative to the screen (view space of a camera), not the
+    /// world.
+    pub fn is_in_screen_space(&self, node: Handle<Node>) -> bool {
+        let mut handle = node;
+        while let Some(current) = self.pool.try_borrow(handle) {
+            if current.screen_space() {
+                return true;
+            }
+            handle = current.parent();
+        }
+        false
+    }
+
+    /// Returns matrix that transforms screen-space coordinates into world coordinates using
+    /// view of active camera. Returns identity matrix if there is no active camera.
+    fn screen_to_world(&self) -> Matrix4<f32> {
+        match self.pool.try_borrow(self.active_camera) {
+            Some(Node::Camera(camera)) => camera
+                .view_matrix()
+                .try_inverse()
+                .unwrap_or_else(Matrix4::identity),
+            _ => Matrix4::identity(),
+        }
+    }
+
+    /// Returns handles of all nodes (except root) that touch a circle in world coordinates. Nodes
+    /// with bounds (sprites) are tested by their [axis-aligned world bounds](Self::world_aabb), so
+    /// rotated nodes could be reported a bit earlier than their actual shape touches the circle.
+    /// Nodes without bounds are tested by their global position. Every node is checked, so the cost
+    /// is linear in the amount of nodes.
+    pub fn query_circle(&self, center: Vector2<f32>, radius: f32) -> Vec<Handle<Node>> {
+        self.pool
+            .pair_iter()
+            .filter(|&(handle, node)| {
+                if handle == self.root {
+                    return false;
+                }
+                let closest = if node.local_bounds().is_some() {
+                    let aabb = self.world_aabb(handle);
+                    Vector2::new(
+                        center.x.max(aabb.x()).min(aabb.x() + aabb.w()),
+                        center.y.max(aabb.y()).min(aabb.y() + aabb.h()),
+                    )
+                } else {
+                    self.compute_global_position(handle)
+                };
+                (closest - center).norm_squared() <= radius * radius
+            })
+            .map(|(handle, _)| handle)
+            .collect()
+    }
+
+    /// Returns handles of all nodes (except root) which global positions are inside circular
+    /// sector with given apex, direction of the bisector, half of opening angle (in radians)
+    /// and radius, in world coordinates. A node exactly at the apex is inside the sector. Returns
+    /// empty list for zero direction, negative angle or radius. Every node is checked, so the
+    /// cost is linear in the amount of nodes.
+    pub fn query_sector(
+        &self,
+        apex: Vector2<f32>,
+        direction: Vector2<f32>,
+        half_angle: f32,
+        radius: f32,
+    ) -> Vec<Handle<Node>> {
+        let direction = match direction.try_normalize(f32::EPSILON) {
+            Some(direction) if half_angle >= 0.0 && radius >= 0.0 => direction,
+            _ => return Vec::new(),
+        };
+        let cos_half_angle = half_angle.min(std::f32::consts::PI).cos();
+        self.pool
+            .pair_iter()
+            .filter(|&(handle, _)| {
+                if handle == self.root {
+                    return false;
+                }
+                let offset = self.compute_global_position(handle) - apex;
+                let distance = offset.norm();
+                distance <= radius
+                    && (distance <= f32::EPSILON
+                        || offset.dot(&direction) / distance >= cos_half_angle)
+            })
+            .map(|(handle, _)| handle)
+            .collect()
+    }
+
+    /// Detaches a node from its parent, keeping it exactly where it is in the world: current global
+    /// transform of the node becomes its local transform (relative to transform of root, if root
+    /// has one) and the node is attached to root. Global transform is calculated on demand, so
+    /// there is no need to update the graph before the call. Transform of a node can not hold skew,
+    /// so if the node was skewed by non-uniformly scaled and rotated ancestors, only closest
+    /// rotation and scale are kept.
+    pub fn bake_global_to_local(&mut self, node: Handle<Node>) {
+        if node == self.root {
+            return;
+        }
+
+        self.relink_keeping_world_transform(node, self.root, usize::MAX);
+    }
+
+    /// Links a node to new parent at given index (see [`Self::link_nodes_at`]) and changes
+    /// its local transform so its world transform stays the same.
+    fn relink_keeping_world_transform(
+        &mut self,
+        node: Handle<Node>,
+        parent: Handle<Node>,
+        index: usize,
+    ) {
+        let global = self.compute_global_transform(node);
+        self.set_local_from_global(node, parent, &global);
+        self.link_nodes_at(node, parent, index);
+    }
+
+    /// Sets local transform of a node so its global transform becomes `global` when the node
+    /// is a child of `parent`, using current local transforms of `parent` and its ancestors.
+    fn set_local_from_global(
+        &mut self,
+        node: Handle<Node>,
+        parent: Handle<Node>,
+        global: &Matrix4<f32>,
+    ) {
+        let m = self
+            .compute_global_transform(parent)
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity)
+            * global;
+        let (translation, rotation, scale) = decompose_transform(&m);
+        let transform = self.pool[node].local_transform_mut();
+        // Pivot of the node is kept, so its contribution to translation must be excluded.
+        let pivot_offset = transform
+            .set_position(Vector2::default())
+            .set_rotation(rotation)
+            .set_scale(scale)
+            .matrix();
+        transform.set_position(translation - Vector2::new(pivot_offset[12], pivot_offset[13]));
+    }
+
+    /// Makes a copy of a subtree starting from `root` that is not inserted in any graph, the
+    /// graph stays untouched. Links between the nodes of the copy are stored as relative
+    /// indices, so the copy stays valid no matter how the graph changes after. Returns empty
+    /// subtree for invalid handle.
+    pub fn clone_subtree_detached(&self, root: Handle<Node>) -> DetachedSubtree {
+        let order = self.render_order(root);
+        let index_of = order
+            .iter()
+            .enumerate()
+            .map(|(index, &handle)| (handle, index))
+            .collect::<HashMap<_, _>>();
+
+        let mut subtree = DetachedSubtree {
+            nodes: Vec::with_capacity(order.len()),
+            parents: Vec::with_capacity(order.len()),
+        };
+        for handle in order {
+            let node = &self.pool[handle];
+            subtree.nodes.push(node.raw_copy());
+            subtree.parents.push(if handle == root {
+                None
+            } else {
+                index_of.get(&node.parent()).copied()
+            });
+        }
+        subtree
+    }
+
+    /// Instantiates a [detached subtree](DetachedSubtree) under `parent`. Every node of the
+    /// subtree gets new handle and new stable id, so the same subtree can be pasted many times.
+    /// Root of pasted subtree gets a name that is unique among its siblings (see
+    /// [`Self::set_unique_name`]). Returns handle of the root of pasted subtree, or
+    /// [`Handle::NONE`] if the subtree is empty.
+    pub fn paste_subtree(&mut self, tree: &DetachedSubtree, parent: Handle<Node>) -> Handle<Node> {
+        let mut handles = Vec::with_capacity(tree.nodes.len());
+        for (node, parent_index) in tree.nodes.iter().zip(tree.parents.iter()) {
+            let handle = self.add_node(node.raw_copy());
+            let parent = parent_index.map_or(parent, |index| handles[index]);
+            self.link_nodes(handle, parent);
+            handles.push(handle);
+        }
+
+        match handles.first() {
+            Some(&root) => {
+                let name = self.pool[root].name().to_owned();
+                self.set_unique_name(root, &name);
+                root
+            }
+            None => Handle::NONE,
+        }
+    }
+
+    /// Returns handles of globally visible nodes (except root) which
+    /// [world bounds](Self::world_aabb) intersect given rectangle, sorted in back-to-front
+    /// [draw order](Self::render_order). Nodes without bounds are tested by their position,
+    /// nodes that are completely [clipped](Self::clip_rect_of) are excluded. This is what a
+    /// renderer needs: culling and sorting in one pass.
+    pub fn visible_sorted(&self, rect: Rect<f32>) -> Vec<Handle<Node>> {
+        self.render_order(self.root)
+            .into_iter()
+            .filter(|&handle| {
+                if handle == self.root || !self.pool[handle].global_visibility() {
+                    return false;
+                }
+                let aabb = self.world_aabb(handle);
+                rect.intersects(aabb)
+                    && self
+                        .clip_rect_of(handle)
+                        .map_or(true, |clip_rect| clip_rect.intersects(aabb))
+            })
+            .collect()
+    }
+
+    /// Updates an instance (a subtree made by [`Self::copy_node`] from `template` graph) to
+    /// match its template. Every node of the instance that has
+    /// [prefab source](crate::scene2d::base::Base::prefab_source) gets all the data of its
+    /// source node, except stable id, links (including
+    /// [rotation constraint](Self::set_rotation_constraint)), spawn frame and runtime-only
+    /// data. Nodes which sources were removed from template are removed from the instance, new
+    /// children of source nodes are copied into the instance.
+    ///
+    /// This is full overwrite: changes made to the instance are lost, except local transform
+    /// of `instance_root` which defines placement of the instance and
+    /// [overrides](Self::set_prefab_override) which are applied again at the end. Overrides of
+    /// nodes that were removed are dropped.
+    pub fn reapply_prefab(&mut self, instance_root: Handle<Node>, template: &Graph) {
+        let instances = self
+            .render_order(instance_root)
+            .into_iter()
+            .filter_map(|handle| {
+                self.pool[handle]
+                    .prefab_source()
+                    .map(|source| (handle, source))
+            })
+            .collect::<Vec<_>>();
+
+        for &(handle, source) in instances.iter() {
+            // Could be removed together with its ancestor.
+            if !self.pool.is_valid_handle(handle) {
+                continue;
+            }
+
+            let source_handle = template.node_by_id(source);
+            if source_handle.is_none() {
+                if handle != instance_root {
+                    self.remove_node(handle);
+                }
+                continue;
+            }
+
+            let old = &mut self.pool[handle];
+            let mut new = template.pool[source_handle].raw_copy();
+            new.id = old.id;
+            new.prefab_source = old.prefab_source;
+            new.prefab_overrides = std::mem::take(&mut old.prefab_overrides);
+            new.parent = old.parent;
+            new.children = std::mem::take(&mut old.children);
+            new.rotation_constraint = old.rotation_constraint;
+            new.spawn_frame = old.spawn_frame;
+            new.take_runtime_data(old);
+            if handle == instance_root {
+                *new.local_transform_mut() = old.local_transform().clone();
+            }
+            new.dirty.set(true);
+            *old = new;
+        }
+
+        for (handle, source) in instances {
+            if !self.pool.is_valid_handle(handle) {
+                continue;
+            }
+            let source_handle = template.node_by_id(source);
+            if source_handle.is_none() {
+                continue;
+            }
+            for &source_child in template.pool[source_handle].children() {
+                let source_child_id = template.pool[source_child].id();
+                let exists = self.pool[handle]
+                    .children()
+                    .iter()
+                    .any(|&child| self.pool[child].prefab_source() == Some(source_child_id));
+                if !exists {
+                    let (copy, _) = template.copy_node(source_child, self, &mut |_, _| true);
+                    self.link_nodes(copy, handle);
+                }
+            }
+        }
+
+        if let Some(root) = self.pool.try_borrow_mut(instance_root) {
+            let mut overrides = std::mem::take(&mut root.prefab_overrides);
+            overrides.retain(|o| {
+                let handle = self.node_by_id(o.node);
+                if self.is_descendant_of(handle, instance_root) {
+                    self.pool[handle].apply_prefab_property(&o.property);
+                    true
+                } else {
+                    false
+                }
+            });
+            self.pool[instance_root].prefab_overrides = overrides;
+        }
+    }
+
+    /// Records instance-specific value of a property of a node (identified by stable id) of
+    /// prefab instance starting from `instance_root` and applies it to the node right away, so
+    /// the value survives [`Self::reapply_prefab`]. Previous override of the same property of
+    /// the node is replaced. Does nothing if `instance_root` is invalid or the node does not
+    /// belong to the instance.
+    pub fn set_prefab_override(
+        &mut self,
+        instance_root: Handle<Node>,
+        node: Uuid,
+        property: PrefabProperty,
+    ) {
+        let handle = self.node_by_id(node);
+        if !self.pool.is_valid_handle(instance_root)
+            || !self.is_descendant_of(handle, instance_root)
+        {
+            return;
+        }
+        self.pool[handle].apply_prefab_property(&property);
+
+        let overrides = &mut self.pool[instance_root].prefab_overrides;
+        let kind = std::mem::discriminant(&property);
+        match overrides
+            .iter_mut()
+            .find(|o| o.node == node && std::mem::discriminant(&o.property) == kind)
+        {
+            Some(existing) => existing.property = property,
+            None => overrides.push(PrefabOverride { node, property }),
+        }
+    }
+
+    /// Removes every override of a node of prefab instance, so the node gets values of its
+    /// template on next [`Self::reapply_prefab`]. Current values of the node are kept.
+    pub fn clear_prefab_overrides(&mut self, instance_root: Handle<Node>, node: Uuid) {
+        if let Some(root) = self.pool.try_borrow_mut(instance_root) {
+            root.prefab_overrides.retain(|o| o.node != node);
+        }
+    }
+
+    /// Returns region (in world coordinates) that a node is clipped by, it is an intersection
+    /// of [world bounds](Self::world_aabb) of every ancestor of the node that has
+    /// [clip](crate::scene2d::base::Base::clip) flag. Parts of the node outside of the region
+    /// are not drawn and should not be hit. Returns `None` if the node is not clipped.
+    pub fn clip_rect_of(&self, node: Handle<Node>) -> Option<Rect<f32>> {
+        let mut clip_rect = None;
+        let mut handle = self.parent(node);
+        while let Some(current) = self.pool.try_borrow(handle) {
+            if current.clip() {
+                let bounds = self.world_aabb(handle);
+                clip_rect = Some(match clip_rect {
+                    Some(clip_rect) => intersect_rects(clip_rect, bounds),
+                    None => bounds,
+                });
+            }
+            handle = current.parent();
+        }
+        clip_rect
+    }
+
+    /// Returns rectangle (in pixels, relative to left-top corner of a frame of given size) that
+    /// encloses a node on screen when the scene is viewed through given camera. Corners of
+    /// [oriented bounds](Self::world_obb) of the node are projected using
+    /// [`Camera::world_to_screen`](crate::scene2d::camera::Camera::world_to_screen).
+    /// [Screen-space](Self::is_in_screen_space) nodes are already in screen coordinates, so
+    /// their bounds are returned as is. If `camera` is not a camera, world bounds of the node
+    /// are returned.
+    pub fn screen_aabb(
+        &self,
+        node: Handle<Node>,
+        camera: Handle<Node>,
+        viewport: Vector2<f32>,
+    ) -> Rect<f32> {
+        if self.is_in_screen_space(node) {
+            return bounding_rect(&self.transformed_obb(node, self.global_transform(node)));
+        }
+
+        match self.pool.try_borrow(camera) {
+            Some(Node::Camera(camera)) => {
+                let obb = self.world_obb(node);
+                bounding_rect(&[
+                    camera.world_to_screen(obb[0], viewport),
+                    camera.world_to_screen(obb[1], viewport),
+                    camera.world_to_screen(obb[2], viewport),
+                    camera.world_to_screen(obb[3], viewport),
+                ])
+            }
+            _ => self.world_aabb(node),
+        }
+    }
+
+    /// Returns handles of all nodes (except root) sorted by distance between their global
+    /// position and given point, nearest first. Every node is included, bounds are not taken
+    /// into account - only position (pivot) of a node matters.
+    pub fn nodes_by_distance(&self, point: Vector2<f32>) -> Vec<Handle<Node>> {
+        let mut nodes = self
+            .pool
+            .pair_iter()
+            .filter(|&(handle, _)| handle != self.root)
+            .map(|(handle, _)| {
+                let m = self.global_transform(handle);
+                let distance = (Vector2::new(m[12], m[13]) - point).norm_squared();
+                (handle, distance)
+            })
+            .collect::<Vec<_>>();
+        nodes.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+        nodes.into_iter().map(|(handle, _)| handle).collect()
+    }
+
+    /// Returns every node together with amount of [updates](Self::update) the node has existed
+    /// for. Diagnostics only: nodes that live longer than expected are likely leaked (someone
+    /// forgot to remove them). Ages are not saved, loaded nodes start from zero.
+    pub fn debug_node_ages(&self) -> Vec<(Handle<Node>, u64)> {
+        self.pool
+            .pair_iter()
+            .map(|(handle, node)| (handle, self.frame - node.spawn_frame))
+            .collect()
+    }
+
+    /// Saves only nodes that satisfy given predicate, plus their ancestors that are needed to
+    /// keep the tree valid. Omitted nodes are just not written, so the saved graph can be
+    /// loaded by usual [`Visit::visit`] call with the same `name`. Handy to skip transient nodes
+    /// (effects, etc.) in save games. Stable ids of saved nodes are preserved.
+    pub fn save_filtered<F>(&self, name: &str, visitor: &mut Visitor, keep: F) -> VisitResult
+    where
+        F: Fn(&Node) -> bool,
+    {
+        let mut kept = HashSet::new();
+        kept.insert(self.root);
+        for (handle, node) in self.pool.pair_iter() {
+            if keep(node) {
+                let mut ancestor = handle;
+                while let Some(current) = self.pool.try_borrow(ancestor) {
+                    if !kept.insert(ancestor) {
+                        break;
+                    }
+                    ancestor = current.parent();
+                }
+            }
+        }
+
+        let mut filtered = Graph::new();
+        let mut root = self.pool[self.root].raw_copy();
+        root.id = self.pool[self.root].id();
+        filtered.pool[filtered.root] = root;
+
+        let mut map = HashMap::new();
+        map.insert(self.root, filtered.root);
+        // Parents go before children in render order, so they're always mapped already.
+        for handle in self.render_order(self.root) {
+            if handle == self.root || !kept.contains(&handle) {
+                continue;
+            }
+            let node = &self.pool[handle];
+            let mut copy = node.raw_copy();
+            copy.id = node.id();
+            copy.prefab_source = node.prefab_source;
+            copy.prefab_overrides = node.prefab_overrides.clone();
+            let copy_handle = filtered.add_node(copy);
+            filtered.link_nodes(copy_handle, map[&node.parent()]);
+            map.insert(handle, copy_handle);
+        }
+
+        for (&handle, &copy_handle) in map.iter() {
+            let copy = &mut filtered.pool[copy_handle];
+            copy.copy_references(&self.pool[handle]);
+            copy.remap_handles(&map);
+        }
+        filtered.active_camera = map
+            .get(&self.active_camera)
+            .copied()
+            .unwrap_or(Handle::NONE);
+        filtered.rebuild_id_map();
+
+        filtered.visit(name, visitor)
+    }
+
+    /// Returns the top-most (first in [hit test order](Self::traverse_hit_test)) node whose
+    /// bounds contain given point in world coordinates, or [`Handle::NONE`] if there is no
+    /// such node. Only visible [pickable](crate::scene2d::base::Base::pickable) nodes with
+    /// bounds (sprites) can be picked, the test is precise for rotated and scaled nodes. Parts
+    /// of nodes that are [clipped](Self::clip_rect_of) out can't be picked.
+    pub fn pick(&self, point: Vector2<f32>) -> Handle<Node> {
+        for (handle, node) in self.traverse_hit_test(self.root) {
+            if !node.pickable() || !self.is_effectively_visible(handle) {
+                continue;
+            }
+            if let Some(clip_rect) = self.clip_rect_of(handle) {
+                if !clip_rect.contains(point) {
+                    continue;
+                }
+            }
+            if let Some(bounds) = node.local_bounds() {
+                let matrix = if self.is_in_screen_space(handle) {
+                    self.screen_to_world() * self.global_transform(handle)
+                } else {
+                    self.global_transform(handle)
+                };
+                if let Some(inverse) = matrix.try_inverse() {
+                    let local = inverse
+                        .transform_point(&Point3::new(point.x, point.y, 0.0))
+                        .coords
+                        .xy();
+                    if bounds.contains(local) {
+                        return handle;
+                    }
+                }
+            }
+        }
+        Handle::NONE
+    }
+
+    /// Returns an iterator over direct children of root - distinct "objects" of the scene, in
+    /// their order. The iterator is empty if the scene has nothing but root.
+    pub fn top_level_objects(&self) -> impl Iterator<Item = Handle<Node>> + '_ {
+        self.pool
+            .try_borrow(self.root)
+            .map(|root| root.children())
+            .unwrap_or_default()
+            .iter()
+            .copied()
+    }
+
+    /// Returns transform of a node expressed in local space of its `ancestor`, by multiplying
+    /// local transforms of the chain from the node up to (but not including) the ancestor.
+    /// Unlike global transform, transforms of the ancestor and everything above it are not
+    /// taken into account. Returns identity matrix if `ancestor` is the node itself or if it is
+    /// not an ancestor of the node (including [`Handle::NONE`]).
+    pub fn transform_relative_to(
+        &self,
+        node: Handle<Node>,
+        ancestor: Handle<Node>,
+    ) -> Matrix4<f32> {
+        if !self.pool.is_valid_handle(ancestor) {
+            return Matrix4::identity();
+        }
+
+        let mut transform = Matrix4::identity();
+        let mut handle = node;
+        while handle != ancestor {
+            match self.pool.try_borrow(handle) {
+                Some(current) => {
+                    transform = current.local_transform().matrix() * transform;
+                    handle = current.parent();
+                }
+                None => return Matrix4::identity(),
+            }
+        }
+        transform
+    }
+
+    /// Returns axis-aligned rectangle in world coordinates that encloses
+    /// [world bounds](Self::world_obb) of every visible node with bounds (sprites). Returns
+    /// empty rectangle at origin (`Rect::default()`) if there are no such nodes. Useful for
+    /// "frame all" and initial camera placement, see
+    /// [`Camera::fit_rect`](crate::scene2d::camera::Camera::fit_rect).
+    pub fn scene_bounds(&self) -> Rect<f32> {
+        let points = self
+            .pool
+            .pair_iter()
+            .filter(|&(handle, node)| {
+                node.local_bounds().is_some() && self.is_effectively_visible(handle)
+            })
+            .flat_map(|(handle, _)| self.world_obb(handle).to_vec())
+            .collect::<Vec<_>>();
+        bounding_rect(&points)
+    }
+
+    /// Returns true if a node has at least one child. Returns false for invalid handle.
+    pub fn has_children(&self, node: Handle<Node>) -> bool {
+        self.pool
+            .try_borrow(node)
+            .map_or(false, |node| !node.children().is_empty())
+    }
+
+    /// Reverses order of children of a node, which flips their draw order. Does nothing for
+    /// invalid handle or a node with less than two children.
+    pub fn reverse_children(&mut self, parent: Handle<Node>) {
+        if let Some(parent) = self.pool.try_borrow_mut(parent) {
+            if parent.children.len() > 1 {
+                parent.children.reverse();
+                self.structure_changed = true;
+            }
+        }
+    }
+
+    /// Wraps sibling nodes into new base node, which is placed at centroid of their world
+    /// positions and takes place of the first of them in draw order. World transforms of the
+    /// nodes are preserved and their relative draw order is kept. Every node must have the
+    /// same parent. Returns handle of the group node. See [`Self::ungroup`] for inverse
+    /// operation.
+    pub fn group(&mut self, handles: &[Handle<Node>]) -> Result<Handle<Node>, GroupError> {
+        let first = *handles.first().ok_or(GroupError::Empty)?;
+        let parent = self.parent(first);
+        for &handle in handles {
+            if handle == self.root || !self.pool.is_valid_handle(handle) {
+                return Err(GroupError::InvalidHandle(handle));
+            }
+            if self.pool[handle].parent() != parent {
+                return Err(GroupError::DifferentParents(handle));
+            }
+        }
+
+        let members = self.pool[parent]
+            .children()
+            .iter()
+            .copied()
+            .filter(|child| handles.contains(child))
+            .collect::<Vec<_>>();
+        let index = self.pool[parent]
+            .children()
+            .iter()
+            .position(|&child| child == members[0])
+            .unwrap_or_default();
+        let centroid = self.centroid(&members);
+
+        let group = BaseBuilder::new().build(self);
+        self.link_nodes_at(group, parent, index);
+        self.set_global_position(group, centroid);
+        for member in members {
+            self.relink_keeping_world_transform(member, group, usize::MAX);
+        }
+        Ok(group)
+    }
+
+    /// Dissolves a group made by [`Self::group`] (or any other node): children of the node
+    /// are moved to its parent in place of the node keeping their world transforms, then the
+    /// node itself is removed. Returns handles of the former children. Does nothing and returns
+    /// empty list for root or invalid handle.
+    pub fn ungroup(&mut self, group: Handle<Node>) -> Vec<Handle<Node>> {
+        if group == self.root || !self.pool.is_valid_handle(group) {
+            return Vec::new();
+        }
+
+        let parent = self.pool[group].parent();
+        let index = self.pool[parent]
+            .children()
+            .iter()
+            .position(|&child| child == group)
+            .unwrap_or_default();
+        let children = self.pool[group].children().to_vec();
+        for (i, &child) in children.iter().enumerate() {
+            self.relink_keeping_world_transform(child, parent, index + i);
+        }
+        self.remove_node(group);
+        children
+    }
+
+    /// Marks (or unmarks) a subtree starting from `root` as static: nodes of the subtree are
+    /// not supposed to move, so a renderer can merge them in a single batch once (see
+    /// [`Self::static_subtrees`]). Movement of a node of static subtree after the subtree was
+    /// updated is reported as a warning in debug builds, because the batch won't be rebuilt.
+    pub fn set_static(&mut self, root: Handle<Node>, is_static: bool) {
+        if let Some(node) = self.pool.try_borrow_mut(root) {
+            node.flags.set(NodeFlags::STATIC, is_static);
+            // Subtree will be baked again on next update, so there is nothing to report yet.
+            for handle in self.render_order(root) {
+                self.pool[handle].static_baked.set(false);
+            }
+        }
+    }
+
+    /// Returns roots of all static subtrees (see [`Self::set_static`]). Static nodes inside
+    /// other static subtrees are not listed, their subtrees are already included.
+    pub fn static_subtrees(&self) -> Vec<Handle<Node>> {
+        self.pool
+            .pair_iter()
+            .filter(|&(_, node)| {
+                if !node.is_static() {
+                    return false;
+                }
+                let mut ancestor = node.parent();
+                while let Some(current) = self.pool.try_borrow(ancestor) {
+                    if current.is_static() {
+                        return false;
+                    }
+                    ancestor = current.parent();
+                }
+                true
+            })
+            .map(|(handle, _)| handle)
+            .collect()
+    }
+
+    /// Returns average world position of given nodes. Positions are calculated on demand (see
+    /// [`Self::compute_global_position`]), so the result is correct even before the graph is
+    /// updated. Invalid handles are skipped, origin is returned if there are no valid handles.
+    pub fn centroid(&self, handles: &[Handle<Node>]) -> Vector2<f32> {
+        let (sum, count) = handles
+            .iter()
+            .filter(|&&handle| self.pool.is_valid_handle(handle))
+            .fold((Vector2::default(), 0usize), |(sum, count), &handle| {
+                (sum + self.compute_global_position(handle), count + 1)
+            });
+        if count > 0 {
+            sum.scale(1.0 / count as f32)
+        } else {
+            Vector2::default()
+        }
+    }
+
+    /// Traverses a subtree starting from `root` and removes every node for which `keep`
+    /// returns false, together with its descendants (like [`Vec::retain`], but hierarchical).
+    /// Descendants of removed nodes are not passed to `keep`. Root of the graph is never
+    /// removed and never passed to `keep`, but its descendants are checked.
+    pub fn retain_subtree<F>(&mut self, root: Handle<Node>, mut keep: F)
+    where
+        F: FnMut(Handle<Node>, &Node) -> bool,
+    {
+        let mut stack = vec![root];
+        while let Some(handle) = stack.pop() {
+            let node = match self.pool.try_borrow(handle) {
+                Some(node) => node,
+                None => continue,
+            };
+            if handle != self.root && !keep(handle, node) {
+                self.remove_node(handle);
+            } else {
+                stack.extend(node.children().iter().rev());
+            }
+        }
+    }
+
+    /// Temporarily links a node to `temp_parent`, runs `f` and then links the node back to its
+    /// original parent at its original position among siblings, with its original local
+    /// transform (which is kept while the node is under `temp_parent` too). Handy to do
+    /// something with a node in a different space, for example to draw an item in UI space.
+    /// If `f` removes the node, there is nothing to restore; if `f` removes the original
+    /// parent, the node stays attached to root. Does nothing (and `f` is not called) for root,
+    /// invalid handles or if `temp_parent` is in the subtree of the node.
+    pub fn with_temporary_parent<F>(&mut self, node: Handle<Node>, temp_parent: Handle<Node>, f: F)
+    where
+        F: FnOnce(&mut Graph),
+    {
+        if node == self.root
+            || !self.pool.is_valid_handle(node)
+            || !self.pool.is_valid_handle(temp_parent)
+            || self.is_descendant_of(temp_parent, node)
+        {
+            return;
+        }
+
+        let parent = self.pool[node].parent();
+        let index = self.pool.try_borrow(parent).map_or(0, |parent| {
+            parent
+                .children()
+                .iter()
+                .position(|&child| child == node)
+                .unwrap_or_default()
+        });
+        let transform = self.pool[node].local_transform().clone();
+
+        self.link_nodes(node, temp_parent);
+        f(self);
+
+        if self.pool.is_valid_handle(node) {
+            let parent = if self.pool.is_valid_handle(parent) {
+                parent
+            } else {
+                self.root
+            };
+            self.link_nodes_at(node, parent, index);
+            *self.pool[node].local_transform_mut() = transform;
+        }
+    }
+
+    /// Returns handles of all nodes that refer to given node: its children (by parent link)
+    /// and nodes that store the handle in their data (see [`Node::referenced_handles`]).
+    /// Useful to warn before removal of a node. Every node is checked, so the cost is linear in
+    /// the amount of nodes.
+    pub fn referencing_nodes(&self, target: Handle<Node>) -> Vec<Handle<Node>> {
+        if target.is_none() {
+            return Vec::new();
+        }
+        self.pool
+            .pair_iter()
+            .filter(|&(_, node)| {
+                node.parent() == target || node.referenced_handles().contains(&target)
+            })
+            .map(|(handle, _)| handle)
+            .collect()
+    }
+
+    /// Attaches debug gizmo to a node, replacing previous one. See [`Self::gizmos`].
+    pub fn set_gizmo(&mut self, node: Handle<Node>, gizmo: Gizmo) {
+        self.pool[node].gizmo = Some(gizmo);
+    }
+
+    /// Detaches debug gizmo from a node and returns it.
+    pub fn remove_gizmo(&mut self, node: Handle<Node>) -> Option<Gizmo> {
+        self.pool
+            .try_borrow_mut(node)
+            .and_then(|node| node.gizmo.take())
+    }
+
+    /// Returns an iterator over every node with a gizmo, items are handle of a node, its gizmo
+    /// and its global transform (which should be used to draw the gizmo). Gizmos of invisible
+    /// nodes are included too, it is up to a debug renderer to filter them.
+    pub fn gizmos(&self) -> impl Iterator<Item = (Handle<Node>, &Gizmo, Matrix4<f32>)> + '_ {
+        self.pool.pair_iter().filter_map(|(handle, node)| {
+            node.gizmo
+                .as_ref()
+                .map(|gizmo| (handle, gizmo, node.global_transform()))
+        })
+    }
+
+    /// Marks a node as dirty, so global data of the node and its whole subtree will be
+    /// recalculated on next [`Self::update_hierarchical_data`] call. Normally there is no
+    /// need to call this, every change made through the API of a node marks it automatically.
+    /// Same as [`Self::invalidate`].
+    pub fn mark_dirty_subtree(&mut self, node: Handle<Node>) {
+        self.invalidate(node);
+    }
+
+    /// Returns handles of all nodes that are exactly `depth` levels below root (root itself is
+    /// at depth 0), in breadth-first order. Returns empty list if the depth exceeds height of
+    /// the tree.
+    pub fn nodes_at_depth(&self, depth: usize) -> Vec<Handle<Node>> {
+        let mut level = vec![self.root];
+        for _ in 0..depth {
+            level = level
+                .iter()
+                .filter_map(|&handle| self.pool.try_borrow(handle))
+                .flat_map(|node| node.children().iter().copied())
+                .collect();
+            if level.is_empty() {
+                break;
+            }
+        }
+        level
+    }
+
+    /// Saves the graph without vacant entries of internal storage: only alive nodes reachable
+    /// from root are written, one after another, with links fixed accordingly, so the file is
+    /// minimal and the loaded graph has no holes. Hierarchy (including order of children) and
+    /// stable ids are preserved exactly, the graph itself stays untouched. The result is loaded
+    /// by usual [`Visit::visit`] call with the same `name`.
+    pub fn save_compacted(&self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        self.save_filtered(name, visitor, |_| true)
+    }
+
+    /// Swaps positions of two subtrees in the hierarchy: `a` takes parent and place among
+    /// siblings of `b` and vice versa, subtrees themselves stay intact. Local transforms are
+    /// kept, so the subtrees are moved along with their new parents. Returns false and does
+    /// nothing if any handle is invalid or root, or if one node is an ancestor of the other one
+    /// (swap would create a cycle).
+    pub fn swap_subtrees(&mut self, a: Handle<Node>, b: Handle<Node>) -> bool {
+        if a == self.root
+            || b == self.root
+            || !self.pool.is_valid_handle(a)
+            || !self.pool.is_valid_handle(b)
+            || self.is_descendant_of(a, b)
+            || self.is_descendant_of(b, a)
+        {
+            return false;
+        }
+
+        let parent_a = self.pool[a].parent();
+        let parent_b = self.pool[b].parent();
+        let (index_a, index_b) =
+            match (self.child_index(parent_a, a), self.child_index(parent_b, b)) {
+                (Some(index_a), Some(index_b)) => (index_a, index_b),
+                _ => return false,
+            };
+
+        self.pool[parent_a].children[index_a] = b;
+        self.pool[parent_b].children[index_b] = a;
+        self.pool[a].parent = parent_b;
+        self.pool[b].parent = parent_a;
+        self.pool[a].dirty.set(true);
+        self.pool[b].dirty.set(true);
+        self.structure_changed = true;
+        true
+    }
+
+    /// Returns position of a child in children list of its parent.
+    fn child_index(&self, parent: Handle<Node>, child: Handle<Node>) -> Option<usize> {
+        self.pool
+            .try_borrow(parent)
+            .and_then(|parent| parent.children().iter().position(|&c| c == child))
+    }
+
+    /// Replaces children list of a node as a whole: each given child is unlinked from its current
+    /// parent and linked to `parent` in given order, previous children that are not in the new list
+    /// are attached to root (for root itself they just stay in the list, before the given
+    /// children). Local transforms of all the nodes are kept. Returns false and does nothing if any
+    /// handle is invalid, a child is root, listed twice, or is `parent` itself or its ancestor
+    /// (that would create a cycle).
+    pub fn set_children(&mut self, parent: Handle<Node>, children: Vec<Handle<Node>>) -> bool {
+        if !self.pool.is_valid_handle(parent) {
+            return false;
+        }
+        let mut unique = HashSet::new();
+        for &child in children.iter() {
+            if child == self.root
+                || !self.pool.is_valid_handle(child)
+                || !unique.insert(child)
+                || self.is_descendant_of(parent, child)
+            {
+                return false;
+            }
+        }
+
+        if parent != self.root {
+            for old_child in self.pool[parent].children().to_vec() {
+                if !unique.contains(&old_child) {
+                    self.link_nodes(old_child, self.root);
+                }
+            }
+        }
+        for child in children {
+            self.link_nodes(child, parent);
+        }
+        true
+    }
+
+    /// Makes a node to be reported as changed (see [`Self::changed_since_last_update`]) only
+    /// when its global position or rotation differ from the ones at the moment of last report
+    /// by more than given threshold, which prevents small jitter from flooding replication
+    /// deltas. Changes of other data (visibility, etc.) that are not accompanied by large
+    /// enough movement are not reported either. Nodes without threshold report any change.
+    pub fn set_change_threshold(
+        &mut self,
+        node: Handle<Node>,
+        position_eps: f32,
+        rotation_eps: f32,
+    ) {
+        let node = &mut self.pool[node];
+        node.change_threshold = Some(ChangeThreshold {
+            position: position_eps,
+            rotation: rotation_eps,
+        });
+        node.reported_state.set(None);
+    }
+
+    /// Removes change threshold of a node, see [`Self::set_change_threshold`].
+    pub fn remove_change_threshold(&mut self, node: Handle<Node>) {
+        let node = &mut self.pool[node];
+        node.change_threshold = None;
+        node.reported_state.set(None);
+    }
+
+    /// Checks whether recalculated node has to be reported as changed, remembers reported
+    /// state of the node if it has change threshold.
+    fn is_change_reportable(&self, node: &Node) -> bool {
+        let threshold = match node.change_threshold {
+            Some(threshold) => threshold,
+            None => return true,
+        };
+        let (position, rotation, _) = decompose_transform(&node.global_transform());
+        if let Some((reported_position, reported_rotation)) = node.reported_state.get() {
+            let delta = rotation - reported_rotation;
+            // Shortest angle between rotations, so wrap-around at PI is not a change.
+            let angle = delta.sin().atan2(delta.cos()).abs();
+            if (position - reported_position).norm() <= threshold.position
+                && angle <= threshold.rotation
+            {
+                return false;
+            }
+        }
+        node.reported_state.set(Some((position, rotation)));
+        true
+    }
+
+    /// Returns route through the hierarchy that connects two nodes: handles from `from` up to
+    /// the closest common ancestor and then down to `to`, both ends (and the common ancestor)
+    /// included. Path from a node to itself consists of the node only. Returns `None` if any
+    /// handle is invalid or the nodes have no common ancestor.
+    pub fn hierarchy_path(
+        &self,
+        from: Handle<Node>,
+        to: Handle<Node>,
+    ) -> Option<Vec<Handle<Node>>> {
+        if !self.pool.is_valid_handle(from) || !self.pool.is_valid_handle(to) {
+            return None;
+        }
+
+        let mut up = Vec::new();
+        let mut handle = from;
+        while let Some(node) = self.pool.try_borrow(handle) {
+            up.push(handle);
+            handle = node.parent();
+        }
+
+        let mut down = Vec::new();
+        let mut handle = to;
+        while let Some(node) = self.pool.try_borrow(handle) {
+            if let Some(common) = up.iter().position(|&ancestor| ancestor == handle) {
+                up.truncate(common + 1);
+                up.extend(down.into_iter().rev());
+                return Some(up);
+            }
+            down.push(handle);
+            handle = node.parent();
+        }
+        None
+    }
+
+    /// Sets or clears given flags on every node that satisfies the predicate, for example to
+    /// hide all decorations in one call. Keep in mind that some flags are inverted, to make
+    /// nodes non-pickable [`NodeFlags::NOT_PICKABLE`] must be set. Nodes which flags were
+    /// actually changed are marked as dirty.
+    pub fn set_flag_where<P>(&mut self, flag: NodeFlags, value: bool, predicate: P)
+    where
+        P: Fn(&Node) -> bool,
+    {
+        for node in self.pool.iter_mut() {
+            if predicate(node) {
+                let mut flags = node.flags();
+                flags.set(flag, value);
+                if flags != node.flags() {
+                    node.set_flags(flags);
+                }
+            }
+        }
+    }
+
+    /// Captures local transform and cached global transform of a node, returns `None` if the
+    /// handle is invalid. The snapshot does not borrow the graph, so it can be stored by undo
+    /// systems or tweens and applied later by [`Self::restore_transform`].
+    pub fn transform_snapshot(&self, node: Handle<Node>) -> Option<TransformSnapshot> {
+        self.pool.try_borrow(node).map(|node| {
+            let transform = node.local_transform();
+            TransformSnapshot {
+                position: transform.position(),
+                rotation: transform.rotation(),
+                scale: transform.scale(),
+                pivot: transform.pivot(),
+                global_transform: node.global_transform(),
+            }
+        })
+    }
+
+    /// Applies a snapshot made by [`Self::transform_snapshot`] to a node. Cached global
+    /// transform is restored too, so it is consistent until next update which recalculates it
+    /// from the (possibly changed) parent. Rotation is clamped to rotation limits of the node.
+    /// Returns false if the handle is invalid.
+    pub fn restore_transform(&mut self, node: Handle<Node>, snapshot: TransformSnapshot) -> bool {
+        match self.pool.try_borrow_mut(node) {
+            Some(node) => {
+                node.local_transform_mut()
+                    .set_position(snapshot.position)
+                    .set_rotation(snapshot.rotation)
+                    .set_scale(snapshot.scale)
+                    .set_pivot(snapshot.pivot);
+                node.global_transform.set(snapshot.global_transform);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns handles of all nodes reachable from root in the order they are visited by
+    /// [`Self::update_hierarchical_data`] - every parent goes before its children, together
+    /// with handles of nodes that can't be reached from root. The second list is empty for a
+    /// well-formed graph; nodes get there if they form a cycle or a subtree that is cut off
+    /// from root (for example its parent does not list it as a child). Every node is yielded
+    /// at most once. If the graph is malformed (there are unreachable nodes, or a link to a
+    /// node that does not exist or to an already visited node is found) a warning is written
+    /// to the log.
+    pub fn topological_order(&self) -> (Vec<Handle<Node>>, Vec<Handle<Node>>) {
+        let (order, malformed) = self.topological_order_checked();
+        let unreachable = if order.len() == self.pool.alive_count() {
+            Vec::new()
+        } else {
+            let reached = order.iter().copied().collect::<HashSet<_>>();
+            self.pool
+                .pair_iter()
+                .map(|(handle, _)| handle)
+                .filter(|handle| !reached.contains(handle))
+                .collect::<Vec<_>>()
+        };
+        if malformed || !unreachable.is_empty() {
+            Log::writeln(
+                MessageKind::Warning,
+                format!(
+                    "Graph has a cycle or a dangling link, {} node(s) can't be reached from \
+                     root: {:?}",
+                    unreachable.len(),
+                    unreachable
+                ),
+            );
+        }
+        (order, unreachable)
+    }
+
+    /// Order of nodes reachable from root, see [`Self::topological_order`]. Does not write
+    /// anything to the log, returns true along with the order if a link to a node that does
+    /// not exist or to an already visited node was found.
+    fn topological_order_checked(&self) -> (Vec<Handle<Node>>, bool) {
+        let mut order = Vec::with_capacity(self.pool.alive_count());
+        let mut visited = HashSet::new();
+        let mut malformed = false;
+        let mut stack = vec![self.root];
+        while let Some(handle) = stack.pop() {
+            match self.pool.try_borrow(handle) {
+                Some(node) => {
+                    if visited.insert(handle) {
+                        order.push(handle);
+                        // Reverse children to pop first child first.
+                        stack.extend(node.children().iter().rev());
+                    } else {
+                        malformed = true;
+                    }
+                }
+                None => malformed = true,
+            }
+        }
+        (order, malformed)
+    }
+
+    /// Writes world transforms computed by an external solver (IK, physics, etc.) back to the
+    /// hierarchy: local transform of every node is back-solved from desired global transform
+    /// and current global transform of its parent. Nodes are processed in
+    /// [topological order](Self::topological_order), so it is fine to pass a parent and its
+    /// children at once, in any order. Skew can not be represented by a local transform, see
+    /// [`Self::global_transform_decomposed`]. Invalid handles are ignored.
+    pub fn set_global_transforms(&mut self, updates: &[(Handle<Node>, Matrix4<f32>)]) {
+        let order = self
+            .topological_order_checked()
+            .0
+            .into_iter()
+            .enumerate()
+            .map(|(index, handle)| (handle, index))
+            .collect::<HashMap<_, _>>();
+        let mut updates = updates
+            .iter()
+            .filter_map(|(node, global)| order.get(node).map(|&index| (index, *node, global)))
+            .collect::<Vec<_>>();
+        updates.sort_by_key(|&(index, _, _)| index);
+
+        for (_, node, global) in updates {
+            let parent = self.pool[node].parent();
+            self.set_local_from_global(node, parent, global);
+        }
+    }
+
+    /// Returns height of a subtree starting from `root` - the number of levels below its
+    /// deepest node, so a node without children has height 0. Returns 0 for invalid handle.
+    pub fn height(&self, root: Handle<Node>) -> usize {
+        let mut height = 0;
+        let mut stack = vec![(root, 0)];
+        while let Some((handle, depth)) = stack.pop() {
+            if let Some(node) = self.pool.try_borrow(handle) {
+                height = height.max(depth);
+                stack.extend(node.children().iter().map(|&child| (child, depth + 1)));
+            }
+        }
+        height
+    }
+}
+
+impl Index<Handle<Node>> for Graph {
+    type Output = Node;
+
+    fn index(&self, index: Handle<Node>) -> &Self::Output {
+        &self.pool[index]
+    }
+}
+
+impl IndexMut<Handle<Node>> for Graph {
+    fn index_mut(&mut self, index: Handle<Node>) -> &mut Self::Output {
+        &mut self.pool[index]
+    }
+}
+
+impl RemapHandles for Graph {
+    fn remap(&mut self, map: &HashMap<Handle<Node>, Handle<Node>>) {
+        for node in self.pool.iter_mut() {
+            node.parent.remap(map);
+            node.children.remap(map);
+            node.remap_handles(map);
+        }
+        self.root.remap(map);
+        self.active_camera.remap(map);
+        for handle in self.id_map.values_mut() {
+            handle.remap(map);
+        }
+        self.changed.remap(map);
+    }
+}
+
+impl Visit for Graph {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.pool.visit("Pool", visitor)?;
+        self.root.visit("Root", visitor)?;
+
+        // Active camera is stored by its stable id, because handle may become invalid
+        // if the pool layout changes.
+        let mut active_camera_id = self
+            .pool
+            .try_borrow(self.active_camera)
+            .map_or_else(Uuid::nil, |camera| camera.id());
+        // Could be missing in files saved by older versions, nil id finds no camera.
+        visit_optional(&mut active_camera_id, "ActiveCamera", visitor)?;
+
+        if visitor.is_reading() {
+            // Id map is not stored, it is rebuilt from ids of nodes.
+            self.rebuild_id_map();
+            self.ensure_root();
+            let active_camera = self.node_by_id(active_camera_id);
+            self.set_active_camera(active_camera);
+        }
+
+        visitor.leave_region()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            algebra::Vector2, color::Color, futures::executor::block_on, math::Rect, pool::Handle,
+            visitor::prelude::*,
+        },
+        scene2d::{
+            base::{BaseBuilder, Gizmo, GizmoShape, PrefabProperty},
+            camera::CameraBuilder,
+            graph::{Graph, LinkError, RemapHandles},
+            sprite::SpriteBuilder,
+            transform::{Transform, TransformBuilder},
+        },
+    };
+    use std::{
+        collections::HashSet,
+        panic::{self, AssertUnwindSafe},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    #[test]
+    fn graph_save_load_active_camera_test() {
+        let mut graph = Graph::new();
+        BaseBuilder::new().build(&mut graph);
+        let camera = CameraBuilder::new(BaseBuilder::new()).build(&mut graph);
+        graph.set_active_camera(camera);
+        let camera_id = graph[camera].id();
+
+        let path = std::env::temp_dir().join("rg3d_graph2d_active_camera_test.bin");
+        let mut visitor = Visitor::new();
+        graph.visit("Graph", &mut visitor).unwrap();
+        visitor.save_binary(&path).unwrap();
+
+        let mut visitor = block_on(Visitor::load_binary(&path)).unwrap();
+        let mut loaded = Graph::default();
+        loaded.visit("Graph", &mut visitor).unwrap();
+
+        let active_camera = loaded.active_camera();
+        assert!(loaded.is_valid_handle(active_camera));
+        assert!(loaded[active_camera].is_camera());
+        assert_eq!(loaded[active_camera].id(), camera_id);
+        assert_eq!(loaded.node_by_id(camera_id), active_camera);
+    }
+
+    #[test]
+    fn graph_load_without_new_fields_test() {
+        let path = std::env::temp_dir().join("rg3d_graph2d_old_format_test.bin");
+        let mut visitor = Visitor::new();
+        visitor.enter_region("Transform").unwrap();
+        let mut position = Vector2::new(1.0f32, 2.0);
+        position.visit("Position", &mut visitor).unwrap();
+        let mut scale = Vector2::new(3.0f32, 4.0);
+        scale.visit("Scale", &mut visitor).unwrap();
+        let mut rotation = 0.5f32;
+        rotation.visit("Rotation", &mut visitor).unwrap();
+        visitor.leave_region().unwrap();
+        visitor.save_binary(&path).unwrap();
+
+        let mut visitor = block_on(Visitor::load_binary(&path)).unwrap();
+        let mut transform = Transform::default();
+        transform.visit("Transform", &mut visitor).unwrap();
+        assert_eq!(transform.position(), Vector2::new(1.0, 2.0));
+        assert_eq!(transform.scale(), Vector2::new(3.0, 4.0));
+        assert_eq!(transform.rotation(), 0.5);
+        assert_eq!(transform.pivot(), Vector2::default());
+        assert_eq!(transform.rotation_limits(), None);
+    }
+
+    #[test]
+    fn graph_dt_camera_shake_test() {
+        let mut graph = Graph::new();
+        let camera = CameraBuilder::new(BaseBuilder::new()).build(&mut graph);
+        graph[camera].as_camera_mut().add_shake(1.0, 0.5);
+
+        graph.update(Vector2::new(100.0, 100.0), 0.25);
+        assert!(graph[camera].as_camera().shake_offset().norm() <= 2.0f32.sqrt());
+
+        // Shake is over once accumulated time steps exceed its duration.
+        graph.update(Vector2::new(100.0, 100.0), 0.3);
+        assert_eq!(graph[camera].as_camera().shake_offset(), Vector2::default());
+    }
+
+    #[test]
+    fn graph_diff_apply_test() {
+        let mut graph = Graph::new();
+        let a = BaseBuilder::new().build(&mut graph);
+        let b = BaseBuilder::new().build(&mut graph);
+        graph.link_nodes(b, a);
+
+        let mut copy = Graph::new();
+        copy.apply_diff(Graph::new().diff(&graph));
+        assert!(copy.validate().is_ok());
+        assert!(copy.diff(&graph).is_empty());
+
+        graph[b]
+            .local_transform_mut()
+            .set_position(Vector2::new(1.0, 2.0));
+        graph.link_nodes(b, graph.get_root());
+        graph.link_nodes(a, b);
+        let c = BaseBuilder::new().build(&mut graph);
+
+        let diff = copy.diff(&graph);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.changed.len(), 2);
+        copy.apply_diff(diff);
+        assert!(copy.validate().is_ok());
+        assert!(copy.diff(&graph).is_empty());
+        assert!(copy.node_by_id(graph[c].id()).is_some());
+    }
+
+    #[test]
+    fn graph_is_mirrored_test() {
+        let mut graph = Graph::new();
+        let parent = BaseBuilder::new()
+            .with_local_transform(
+                TransformBuilder::new()
+                    .with_scale(Vector2::new(-1.0, 1.0))
+                    .build(),
+            )
+            .build(&mut graph);
+        let child = BaseBuilder::new().build(&mut graph);
+        graph.link_nodes(child, parent);
+        graph.update_hierarchical_data();
+
+        assert!(graph.is_mirrored(parent));
+        assert!(graph.is_mirrored(child));
+        assert!(!graph.is_mirrored(graph.get_root()));
+
+        graph[child]
+            .local_transform_mut()
+            .set_scale(Vector2::new(1.0, -1.0));
+        graph.update_hierarchical_data();
+        assert!(!graph.is_mirrored(child));
+    }
+
+    #[test]
+    fn graph_disabled_subtree_callback_test() {
+        let mut graph = Graph::new();
+        let parent = BaseBuilder::new().build(&mut graph);
+        let child = BaseBuilder::new().build(&mut graph);
+        graph.link_nodes(child, parent);
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        graph.set_update_callback(
+            child,
+            Box::new(move |_, _| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }),
+        );
+
+        graph.update(Vector2::new(1.0, 1.0), 0.1);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        graph[parent].set_enabled(false);
+        graph.update(Vector2::new(1.0, 1.0), 0.1);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        graph[parent].set_enabled(true);
+        graph.update(Vector2::new(1.0, 1.0), 0.1);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn graph_root_transform_test() {
+        let mut graph = Graph::new();
+        let node = BaseBuilder::new()
+            .with_local_transform(
+                TransformBuilder::new()
+                    .with_position(Vector2::new(1.0, 2.0))
+                    .build(),
+            )
+            .build(&mut graph);
+        let root = graph.get_root();
+        graph[root]
+            .local_transform_mut()
+            .set_position(Vector2::new(10.0, 20.0))
+            .set_scale(Vector2::new(2.0, 2.0));
+        graph.update_hierarchical_data();
+
+        assert_eq!(graph[node].global_position(), Vector2::new(12.0, 24.0));
+        assert_eq!(
+            graph.compute_global_position(node),
+            Vector2::new(12.0, 24.0)
+        );
+
+        graph.bake_global_to_local(node);
+        assert_eq!(
+            graph.compute_global_position(node),
+            Vector2::new(12.0, 24.0)
+        );
+    }
+
+    #[test]
+    fn graph_retain_subtree_test() {
+        let mut graph = Graph::new();
+        let a = BaseBuilder::new()
+            .with_name("a".to_owned())
+            .build(&mut graph);
+        let b = BaseBuilder::new()
+            .with_name("dead".to_owned())
+            .build(&mut graph);
+        let c = BaseBuilder::new()
+            .with_name("c".to_owned())
+            .build(&mut graph);
+        graph.link_nodes(b, a);
+        graph.link_nodes(c, b);
+
+        let mut visited = Vec::new();
+        graph.retain_subtree(graph.get_root(), |handle, node| {
+            visited.push(handle);
+            node.name() != "dead"
+        });
+
+        assert_eq!(visited, vec![a, b]);
+        assert!(graph.is_valid_handle(a));
+        assert!(!graph.is_valid_handle(b));
+        assert!(!graph.is_valid_handle(c));
+        assert!(graph[a].children().is_empty());
+        assert!(graph.validate().is_ok());
+    }
+
+    #[test]
+    fn graph_selective_update_test() {
+        let mut graph = Graph::new();
+        let parent = BaseBuilder::new().build(&mut graph);
+        let a = BaseBuilder::new().build(&mut graph);
+        let b = BaseBuilder::new().build(&mut graph);
+        graph.link_nodes(a, parent);
+        graph.link_nodes(b, parent);
+        graph.update_hierarchical_data();
+
+        graph[a]
+            .local_transform_mut()
+            .set_position(Vector2::new(1.0, 2.0));
+        graph.update_hierarchical_data();
+        assert_eq!(graph.changed_since_last_update(), &[a]);
+        assert_eq!(graph[a].global_position(), Vector2::new(1.0, 2.0));
+
+        graph.update_hierarchical_data();
+        assert!(graph.changed_since_last_update().is_empty());
+
+        graph.mark_dirty_subtree(parent);
+        graph.update_hierarchical_data();
+        assert_eq!(graph.changed_since_last_update(), &[parent, a, b]);
+    }
+
+    #[test]
+    fn graph_reset_transform_test() {
+        let mut graph = Graph::new();
+        let node = BaseBuilder::new()
+            .with_local_transform(
+                TransformBuilder::new()
+                    .with_position(Vector2::new(1.0, 2.0))
+                    .with_rotation(0.5)
+                    .with_scale(Vector2::new(3.0, 3.0))
+                    .with_pivot(Vector2::new(4.0, 5.0))
+                    .with_rotation_limits(-1.0, 1.0)
+                    .build(),
+            )
+            .build(&mut graph);
+
+        let previous = graph.reset_transform(node).unwrap();
+        assert_eq!(previous.position(), Vector2::new(1.0, 2.0));
+        assert_eq!(previous.rotation(), 0.5);
+
+        let transform = graph[node].local_transform();
+        assert_eq!(transform.position(), Vector2::default());
+        assert_eq!(transform.rotation(), 0.0);
+        assert_eq!(transform.scale(), Vector2::new(1.0, 1.0));
+        assert_eq!(transform.pivot(), Vector2::new(4.0, 5.0));
+        assert_eq!(transform.rotation_limits(), Some((-1.0, 1.0)));
+    }
+
+    #[test]
+    fn graph_nested_clip_test() {
+        let mut graph = Graph::new();
+        let outer = BaseBuilder::new()
+            .with_clip(true)
+            .with_bounds_override(Rect::new(0.0, 0.0, 10.0, 10.0))
+            .build(&mut graph);
+        let inner = BaseBuilder::new()
+            .with_clip(true)
+            .with_bounds_override(Rect::new(5.0, 5.0, 10.0, 10.0))
+            .build(&mut graph);
+        let leaf = BaseBuilder::new().build(&mut graph);
+        graph.link_nodes(inner, outer);
+        graph.link_nodes(leaf, inner);
+        graph.update_hierarchical_data();
+
+        assert_eq!(graph.clip_rect_of(outer), None);
+        assert_eq!(
+            graph.clip_rect_of(inner),
+            Some(Rect::new(0.0, 0.0, 10.0, 10.0))
+        );
+        assert_eq!(
+            graph.clip_rect_of(leaf),
+            Some(Rect::new(5.0, 5.0, 5.0, 5.0))
+        );
+
+        // Disjoint clip rects leave nothing visible.
+        graph[inner].set_bounds_override(Some(Rect::new(20.0, 20.0, 10.0, 10.0)));
+        graph.update_hierarchical_data();
+        let clip_rect = graph.clip_rect_of(leaf).unwrap();
+        assert_eq!(clip_rect.w(), 0.0);
+        assert_eq!(clip_rect.h(), 0.0);
+    }
+
+    #[test]
+    fn graph_pick_clip_test() {
+        let mut graph = Graph::new();
+        let clipper = BaseBuilder::new()
+            .with_clip(true)
+            .with_bounds_override(Rect::new(0.0, 0.0, 10.0, 10.0))
+            .build(&mut graph);
+        let sprite = SpriteBuilder::new(BaseBuilder::new())
+            .with_size(20.0)
+            .build(&mut graph);
+        graph.link_nodes(sprite, clipper);
+        graph.update_hierarchical_data();
+
+        assert_eq!(graph.pick(Vector2::new(5.0, 5.0)), sprite);
+        assert_eq!(graph.pick(Vector2::new(15.0, 15.0)), Handle::NONE);
+
+        graph[clipper].set_clip(false);
+        graph.update_hierarchical_data();
+        assert_eq!(graph.pick(Vector2::new(15.0, 15.0)), sprite);
+    }
+
+    #[test]
+    fn graph_constraint_keeps_changes_test() {
+        let mut graph = Graph::new();
+        let target = BaseBuilder::new().build(&mut graph);
+        let constrained = BaseBuilder::new().build(&mut graph);
+        let moved = BaseBuilder::new().build(&mut graph);
+        graph.set_rotation_constraint(constrained, target);
+        graph.update(Vector2::new(1.0, 1.0), 0.1);
+
+        graph[target].local_transform_mut().set_rotation(0.5);
+        graph[moved]
+            .local_transform_mut()
+            .set_position(Vector2::new(1.0, 2.0));
+        graph.update(Vector2::new(1.0, 1.0), 0.1);
+
+        let changed = graph.changed_since_last_update();
+        assert!(changed.contains(&target));
+        assert!(changed.contains(&moved));
+        assert!(changed.contains(&constrained));
+        assert!((graph[constrained].local_transform().rotation() - 0.5).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn graph_change_threshold_test() {
+        let mut graph = Graph::new();
+        let node = BaseBuilder::new().build(&mut graph);
+        graph.set_change_threshold(node, 1.0, 0.1);
+        graph.update_hierarchical_data();
+        assert!(graph.changed_since_last_update().contains(&node));
+
+        // Jitter below threshold is not reported.
+        graph[node]
+            .local_transform_mut()
+            .set_position(Vector2::new(0.5, 0.0));
+        graph.update_hierarchical_data();
+        assert!(graph.changed_since_last_update().is_empty());
+
+        // Distance is measured from last reported state, not from previous update.
+        graph[node]
+            .local_transform_mut()
+            .set_position(Vector2::new(1.5, 0.0));
+        graph.update_hierarchical_data();
+        assert_eq!(graph.changed_since_last_update(), &[node]);
+    }
+
+    #[test]
+    fn graph_pick_rotated_test() {
+        let mut graph = Graph::new();
+        let sprite = SpriteBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_rotation(std::f32::consts::FRAC_PI_4)
+                    .build(),
+            ),
+        )
+        .with_size(10.0)
+        .build(&mut graph);
+        graph.update_hierarchical_data();
+
+        assert_eq!(graph.pick(Vector2::new(0.0, 7.0)), sprite);
+        // Inside of world AABB, but outside of the rotated sprite.
+        assert_eq!(graph.pick(Vector2::new(7.0, 0.0)), Handle::NONE);
+
+        graph[sprite].set_pickable(false);
+        assert_eq!(graph.pick(Vector2::new(0.0, 7.0)), Handle::NONE);
+    }
+
+    #[test]
+    fn graph_pick_screen_space_test() {
+        let mut graph = Graph::new();
+        let camera = CameraBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_position(Vector2::new(100.0, 0.0))
+                    .build(),
+            ),
+        )
+        .build(&mut graph);
+        graph.set_active_camera(camera);
+        let hud = SpriteBuilder::new(BaseBuilder::new().with_screen_space(true))
+            .with_size(10.0)
+            .build(&mut graph);
+        graph.update(Vector2::new(200.0, 200.0), 0.0);
+
+        assert_eq!(graph.pick(Vector2::new(105.0, 5.0)), hud);
+        assert_eq!(graph.pick(Vector2::new(5.0, 5.0)), Handle::NONE);
+    }
+
+    #[test]
+    fn graph_prefab_override_test() {
+        let mut template = Graph::new();
+        let enemy = SpriteBuilder::new(BaseBuilder::new()).build(&mut template);
+        let part = SpriteBuilder::new(BaseBuilder::new())
+            .with_color(Color::WHITE)
+            .build(&mut template);
+        template.link_nodes(part, enemy);
+
+        let mut graph = Graph::new();
+        let (instance, map) = template.copy_node(enemy, &mut graph, &mut |_, _| true);
+        let instance_part = map[&part];
+        let part_id = graph[instance_part].id();
+        graph.set_prefab_override(instance, part_id, PrefabProperty::Color(Color::RED));
+        assert_eq!(graph[instance_part].as_sprite().color(), Color::RED);
+
+        template[part].as_sprite_mut().set_color(Color::BLUE);
+        template[part]
+            .local_transform_mut()
+            .set_position(Vector2::new(1.0, 2.0));
+        graph.reapply_prefab(instance, &template);
+
+        let instance_part = graph.node_by_id(part_id);
+        assert_eq!(graph[instance_part].as_sprite().color(), Color::RED);
+        assert_eq!(
+            graph[instance_part].local_transform().position(),
+            Vector2::new(1.0, 2.0)
+        );
+        assert_eq!(graph[instance].prefab_overrides().len(), 1);
+
+        graph.clear_prefab_overrides(instance, part_id);
+        graph.reapply_prefab(instance, &template);
+        assert_eq!(graph[instance_part].as_sprite().color(), Color::BLUE);
+    }
+
+    #[test]
+    fn graph_reapply_prefab_keeps_gizmo_test() {
+        let mut template = Graph::new();
+        let enemy = BaseBuilder::new().build(&mut template);
+
+        let mut graph = Graph::new();
+        let (instance, _) = template.copy_node(enemy, &mut graph, &mut |_, _| true);
+        let gizmo = Gizmo {
+            shape: GizmoShape::Circle { radius: 2.0 },
+            color: Color::RED,
+        };
+        graph.set_gizmo(instance, gizmo);
+
+        graph.reapply_prefab(instance, &template);
+        assert_eq!(graph[instance].gizmo(), Some(&gizmo));
+    }
+
+    #[test]
+    fn graph_reapply_prefab_keeps_instance_data_test() {
+        let mut template = Graph::new();
+        let enemy = BaseBuilder::new().build(&mut template);
+
+        let mut graph = Graph::new();
+        let target = BaseBuilder::new().build(&mut graph);
+        graph.update(Vector2::new(1.0, 1.0), 0.1);
+        let (instance, _) = template.copy_node(enemy, &mut graph, &mut |_, _| true);
+        graph.set_rotation_constraint(instance, target);
+        graph.update(Vector2::new(1.0, 1.0), 0.1);
+        graph.update(Vector2::new(1.0, 1.0), 0.1);
+
+        graph.reapply_prefab(instance, &template);
+        assert_eq!(
+            graph[instance].rotation_constraint().unwrap().target,
+            target
+        );
+        let age = graph
+            .debug_node_ages()
+            .into_iter()
+            .find(|&(handle, _)| handle == instance)
+            .map(|(_, age)| age);
+        assert_eq!(age, Some(2));
+    }
+
+    #[test]
+    fn graph_topological_order_detached_cycle_test() {
+        let mut graph = Graph::new();
+        let linked = BaseBuilder::new().build(&mut graph);
+        let a = BaseBuilder::new().build(&mut graph);
+        let b = BaseBuilder::new().build(&mut graph);
+
+        // Cut a and b off from root and make them reference each other.
+        let root = graph.get_root();
+        graph[root]
+            .children
+            .retain(|&child| child != a && child != b);
+        graph[a].parent = b;
+        graph[a].children = vec![b];
+        graph[b].parent = a;
+        graph[b].children = vec![a];
+
+        let (order, unreachable) = graph.topological_order();
+        assert_eq!(order, vec![root, linked]);
+        assert_eq!(unreachable.len(), 2);
+        assert!(unreachable.contains(&a));
+        assert!(unreachable.contains(&b));
+    }
+
+    #[test]
+    fn graph_link_children_test() {
+        let mut graph = Graph::new();
+        let parent = BaseBuilder::new().build(&mut graph);
+        let a = BaseBuilder::new().build(&mut graph);
+        let b = BaseBuilder::new().build(&mut graph);
+        graph.link_nodes(b, a);
+
+        // Parent lies in the subtree of b, nothing is linked.
+        graph.link_nodes(parent, b);
+        assert_eq!(graph.link_children(parent, &[a, b]), Err(LinkError::Cycle));
+        assert_eq!(graph[b].parent(), a);
+        assert!(graph[parent].children().is_empty());
+
+        graph.link_nodes(parent, graph.get_root());
+        assert_eq!(graph.link_children(parent, &[a, b]), Ok(()));
+        assert_eq!(graph[parent].children(), &[a, b]);
+        assert_eq!(graph[b].parent(), parent);
+    }
+
+    #[test]
+    fn graph_compact_with_test() {
+        let mut graph = Graph::new();
+        let removed = BaseBuilder::new().build(&mut graph);
+        let parent = BaseBuilder::new().build(&mut graph);
+        let mut child = BaseBuilder::new().build(&mut graph);
+        graph.link_nodes(child, parent);
+        graph.remove_node(removed);
+        let child_id = graph[child].id();
+        let parent_id = graph[parent].id();
+
+        let mut list = vec![parent, removed];
+        {
+            let mut others: [&mut dyn RemapHandles; 2] = [&mut child, &mut list];
+            graph.compact_with(&mut others);
+        }
+
+        assert_eq!(graph.capacity(), graph.node_count());
+        assert_eq!(graph[child].id(), child_id);
+        assert_eq!(graph[list[0]].id(), parent_id);
+        assert_eq!(list[1], Handle::NONE);
+        assert_eq!(graph[child].parent(), list[0]);
+        assert!(graph.validate().is_ok());
+    }
+
+    #[test]
+    fn graph_batch_edit_test() {
+        let mut graph = Graph::new();
+        let node = BaseBuilder::new().build(&mut graph);
+        graph.update_hierarchical_data();
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        graph.watch_transform(
+            node,
+            Box::new(move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }),
+        );
+
+        graph.batch_edit(|graph| {
+            for i in 1..4 {
+                graph[node]
+                    .local_transform_mut()
+                    .set_position(Vector2::new(i as f32, 0.0));
+                graph.update_hierarchical_data();
+            }
+            graph.batch_edit(|graph| {
+                graph[node]
+                    .local_transform_mut()
+                    .offset(Vector2::new(0.0, 1.0));
+            });
+            assert!(graph.is_batch_editing());
+            assert_eq!(graph[node].global_position(), Vector2::default());
+        });
+
+        // A single update at the end of the outermost batch.
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(graph[node].global_position(), Vector2::new(3.0, 1.0));
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            graph.batch_edit(|_| panic!("edit failed"));
+        }));
+        assert!(result.is_err());
+        assert!(!graph.is_batch_editing());
+    }
+
+    #[test]
+    fn graph_world_obb_test() {
+        let mut graph = Graph::new();
+        let sprite = SpriteBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_position(Vector2::new(5.0, 0.0))
+                    .with_rotation(std::f32::consts::FRAC_PI_2)
+                    .build(),
+            ),
+        )
+        .with_size(10.0)
+        .build(&mut graph);
+        let point = BaseBuilder::new()
+            .with_local_transform(
+                TransformBuilder::new()
+                    .with_position(Vector2::new(1.0, 2.0))
+                    .build(),
+            )
+            .build(&mut graph);
+        graph.update_hierarchical_data();
+
+        let expected = [
+            Vector2::new(5.0, 0.0),
+            Vector2::new(5.0, 10.0),
+            Vector2::new(-5.0, 10.0),
+            Vector2::new(-5.0, 0.0),
+        ];
+        for (corner, expected) in graph.world_obb(sprite).iter().zip(expected.iter()) {
+            assert!((corner - expected).norm() < 1.0e-5);
+        }
+        assert_eq!(graph.world_obb(point), [Vector2::new(1.0, 2.0); 4]);
+    }
+
+    #[test]
+    fn graph_overlaps_rotated_test() {
+        let mut graph = Graph::new();
+        let diamond = SpriteBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_rotation(std::f32::consts::FRAC_PI_4)
+                    .build(),
+            ),
+        )
+        .with_size(10.0)
+        .build(&mut graph);
+        let other = SpriteBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_position(Vector2::new(5.0, 1.0))
+                    .build(),
+            ),
+        )
+        .with_size(2.0)
+        .build(&mut graph);
+        graph.update_hierarchical_data();
+
+        // World AABBs intersect, but rotated box does not touch the other one.
+        assert!(graph
+            .world_aabb(diamond)
+            .intersects(graph.world_aabb(other)));
+        assert!(!graph.overlaps(diamond, other));
+        assert!(!graph.overlaps(other, diamond));
+
+        graph[other]
+            .local_transform_mut()
+            .set_position(Vector2::new(1.0, 5.0));
+        graph.update_hierarchical_data();
+        assert!(graph.overlaps(diamond, other));
+    }
+
+    #[test]
+    fn graph_clone_subtree_detached_test() {
+        let mut graph = Graph::new();
+        let root = BaseBuilder::new()
+            .with_name("root".to_owned())
+            .build(&mut graph);
+        let a = BaseBuilder::new().build(&mut graph);
+        let b = BaseBuilder::new().build(&mut graph);
+        graph.link_nodes(a, root);
+        graph.link_nodes(b, a);
+        let count = graph.node_count();
+
+        let tree = graph.clone_subtree_detached(root);
+        assert_eq!(graph.node_count(), count);
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.parent_index(0), None);
+        assert_eq!(tree.parent_index(1), Some(0));
+        assert_eq!(tree.parent_index(2), Some(1));
+
+        // The copy is not affected by changes of the graph.
+        graph[root].set_name("changed");
+        graph.remove_node(b);
+        assert_eq!(tree.root().unwrap().name(), "root");
+        assert_eq!(tree.len(), 3);
+
+        assert!(graph.clone_subtree_detached(b).is_empty());
+    }
+
+    #[test]
+    fn graph_paste_subtree_test() {
+        let mut graph = Graph::new();
+        let root = BaseBuilder::new()
+            .with_name("item".to_owned())
+            .build(&mut graph);
+        let child = BaseBuilder::new()
+            .with_name("child".to_owned())
+            .build(&mut graph);
+        graph.link_nodes(child, root);
+        let tree = graph.clone_subtree_detached(root);
+
+        let target = BaseBuilder::new().build(&mut graph);
+        let first = graph.paste_subtree(&tree, target);
+        let second = graph.paste_subtree(&tree, target);
+        assert!(graph.validate().is_ok());
+        assert_eq!(graph[target].children(), &[first, second]);
+
+        let mut ids = HashSet::new();
+        ids.insert(graph[root].id());
+        ids.insert(graph[child].id());
+        for &pasted in [first, second].iter() {
+            assert_eq!(graph[pasted].children().len(), 1);
+            let pasted_child = graph[pasted].children()[0];
+            assert_eq!(graph[pasted_child].name(), "child");
+            assert_eq!(graph[pasted_child].parent(), pasted);
+            assert!(ids.insert(graph[pasted].id()));
+            assert!(ids.insert(graph[pasted_child].id()));
+        }
+        assert_eq!(graph.node_by_id(graph[first].id()), first);
+
+        // Pasted roots get unique names among siblings.
+        assert_eq!(graph[first].name(), "item");
+        assert_eq!(graph[second].name(), "item_1");
+    }
+
+    #[test]
+    fn graph_save_filtered_test() {
+        let mut graph = Graph::new();
+        let kept = BaseBuilder::new().build(&mut graph);
+        let skipped = BaseBuilder::new().with_visibility(false).build(&mut graph);
+        let ancestor = BaseBuilder::new().with_visibility(false).build(&mut graph);
+        let leaf = BaseBuilder::new().build(&mut graph);
+        graph.link_nodes(skipped, kept);
+        graph.link_nodes(leaf, ancestor);
+
+        let path = std::env::temp_dir().join("rg3d_graph2d_save_filtered_test.bin");
+        let mut visitor = Visitor::new();
+        graph
+            .save_filtered("Graph", &mut visitor, |node| node.visibility())
+            .unwrap();
+        visitor.save_binary(&path).unwrap();
+
+        let mut visitor = block_on(Visitor::load_binary(&path)).unwrap();
+        let mut loaded = Graph::default();
+        loaded.visit("Graph", &mut visitor).unwrap();
+        assert!(loaded.validate().is_ok());
+        assert_eq!(loaded.node_count(), 4);
+        assert!(loaded.node_by_id(graph[skipped].id()).is_none());
+
+        // Invisible ancestor is saved to keep the tree valid.
+        let loaded_kept = loaded.node_by_id(graph[kept].id());
+        let loaded_ancestor = loaded.node_by_id(graph[ancestor].id());
+        let loaded_leaf = loaded.node_by_id(graph[leaf].id());
+        assert!(loaded[loaded_kept].children().is_empty());
+        assert_eq!(loaded[loaded_leaf].parent(), loaded_ancestor);
+        assert!(!loaded[loaded_ancestor].visibility());
+    }
+
+    #[test]
+    fn graph_save_compacted_test() {
+        let mut graph = Graph::new();
+        let removed = BaseBuilder::new().build(&mut graph);
+        let parent = BaseBuilder::new().build(&mut graph);
+        let first = BaseBuilder::new().build(&mut graph);
+        let second = BaseBuilder::new().build(&mut graph);
+        graph.link_nodes(second, parent);
+        graph.link_nodes(first, parent);
+        graph.remove_node(removed);
+        let capacity = graph.capacity();
+
+        let path = std::env::temp_dir().join("rg3d_graph2d_save_compacted_test.bin");
+        let mut visitor = Visitor::new();
+        graph.save_compacted("Graph", &mut visitor).unwrap();
+        visitor.save_binary(&path).unwrap();
+        assert_eq!(graph.capacity(), capacity);
+
+        let mut visitor = block_on(Visitor::load_binary(&path)).unwrap();
+        let mut loaded = Graph::default();
+        loaded.visit("Graph", &mut visitor).unwrap();
+        assert!(loaded.validate().is_ok());
+        assert_eq!(loaded.capacity(), loaded.node_count());
+        assert_eq!(loaded.node_count(), graph.node_count());
+
+        let loaded_parent = loaded.node_by_id(graph[parent].id());
+        let children = loaded[loaded_parent]
+            .children()
+            .iter()
+            .map(|&child| loaded[child].id())
+            .collect::<Vec<_>>();
+        assert_eq!(children, vec![graph[second].id(), graph[first].id()]);
+    }
+
+    #[test]
+    fn graph_group_ungroup_test() {
+        let mut graph = Graph::new();
+        let parent = BaseBuilder::new()
+            .with_local_transform(
+                TransformBuilder::new()
+                    .with_position(Vector2::new(10.0, 5.0))
+                    .with_rotation(0.3)
+                    .with_scale(Vector2::new(2.0, 2.0))
+                    .build(),
+            )
+            .build(&mut graph);
+        let mut make_child = |position: Vector2<f32>, rotation: f32| {
+            let child = BaseBuilder::new()
+                .with_local_transform(
+                    TransformBuilder::new()
+                        .with_position(position)
+                        .with_rotation(rotation)
+                        .build(),
+                )
+                .build(&mut graph);
+            graph.link_nodes(child, parent);
+            child
+        };
+        let a = make_child(Vector2::new(2.0, 0.0), 0.0);
+        let c = make_child(Vector2::new(-3.0, 1.0), 0.5);
+        let b = make_child(Vector2::new(0.0, 4.0), 1.0);
+        let globals = [a, b, c]
+            .iter()
+            .map(|&node| graph.compute_global_transform(node))
+            .collect::<Vec<_>>();
+        let assert_world_transforms_kept = |graph: &Graph| {
+            for (&node, global) in [a, b, c].iter().zip(globals.iter()) {
+                assert!((graph.compute_global_transform(node) - global).norm() < 1.0e-4);
+            }
+        };
+
+        let group = graph.group(&[b, a]).unwrap();
+        assert_eq!(graph[parent].children(), &[group, c]);
+        assert_eq!(graph[group].children(), &[a, b]);
+        assert!((graph.compute_global_position(group) - graph.centroid(&[a, b])).norm() < 1.0e-4);
+        assert_world_transforms_kept(&graph);
+
+        assert_eq!(graph.ungroup(group), vec![a, b]);
+        assert!(!graph.is_valid_handle(group));
+        assert_eq!(graph[parent].children(), &[a, b, c]);
+        assert_world_transforms_kept(&graph);
+
+        assert!(graph.group(&[a, parent]).is_err());
+    }
+
+    #[test]
+    fn graph_swap_subtrees_test() {
+        let mut graph = Graph::new();
+        let left = BaseBuilder::new().build(&mut graph);
+        let right = BaseBuilder::new().build(&mut graph);
+        let a = BaseBuilder::new().build(&mut graph);
+        let a_child = BaseBuilder::new().build(&mut graph);
+        let other = BaseBuilder::new().build(&mut graph);
+        let b = BaseBuilder::new().build(&mut graph);
+        graph.link_nodes(a, left);
+        graph.link_nodes(a_child, a);
+        graph.link_nodes(other, right);
+        graph.link_nodes(b, right);
+
+        assert!(graph.swap_subtrees(a, b));
+        assert_eq!(graph[left].children(), &[b]);
+        assert_eq!(graph[right].children(), &[other, a]);
+        assert_eq!(graph[a].parent(), right);
+        assert_eq!(graph[b].parent(), left);
+        assert_eq!(graph[a].children(), &[a_child]);
+
+        // Swapping a node with its descendant would create a cycle.
+        assert!(!graph.swap_subtrees(a, a_child));
+        assert!(!graph.swap_subtrees(a_child, right));
+        assert!(!graph.swap_subtrees(a, graph.get_root()));
+        assert_eq!(graph[a].children(), &[a_child]);
+        assert!(graph.validate().is_ok());
+    }
+
+    #[test]
+    fn graph_set_children_test() {
+        let mut graph = Graph::new();
+        let root = graph.get_root();
+        let parent = BaseBuilder::new().build(&mut graph);
+        let old = BaseBuilder::new().build(&mut graph);
+        let a = BaseBuilder::new()
+            .with_local_transform(
+                TransformBuilder::new()
+                    .with_position(Vector2::new(1.0, 2.0))
+                    .build(),
+            )
+            .build(&mut graph);
+        let b = BaseBuilder::new().build(&mut graph);
+        graph.link_nodes(old, parent);
+        graph.link_nodes(a, parent);
+
+        assert!(graph.set_children(parent, vec![b, a]));
+        assert_eq!(graph[parent].children(), &[b, a]);
+        assert_eq!(graph[old].parent(), root);
+        assert_eq!(
+            graph[a].local_transform().position(),
+            Vector2::new(1.0, 2.0)
+        );
+
+        // Invalid lists leave the graph untouched.
+        let grandchild = BaseBuilder::new().build(&mut graph);
+        graph.link_nodes(grandchild, a);
+        assert!(!graph.set_children(a, vec![parent]));
+        assert!(!graph.set_children(grandchild, vec![a]));
+        assert!(!graph.set_children(parent, vec![a, a]));
+        assert!(!graph.set_children(parent, vec![parent]));
+        assert!(!graph.set_children(parent, vec![root]));
+        assert_eq!(graph[parent].children(), &[b, a]);
+        assert!(graph.validate().is_ok());
+    }
+
+    #[test]
+    fn graph_set_global_transforms_test() {
+        let mut graph = Graph::new();
+        let parent = BaseBuilder::new()
+            .with_local_transform(
+                TransformBuilder::new()
+                    .with_position(Vector2::new(-5.0, 0.0))
+                    .with_scale(Vector2::new(2.0, 2.0))
+                    .build(),
+            )
+            .build(&mut graph);
+        let child = BaseBuilder::new().build(&mut graph);
+        graph.link_nodes(child, parent);
+        graph.update_hierarchical_data();
+
+        let parent_global = TransformBuilder::new()
+            .with_position(Vector2::new(10.0, 0.0))
+            .with_rotation(0.5)
+            .build()
+            .matrix();
+        let child_global = TransformBuilder::new()
+            .with_position(Vector2::new(3.0, 4.0))
+            .with_rotation(1.0)
+            .build()
+            .matrix();
+        // Child goes first, but must be solved against new transform of its parent.
+        graph.set_global_transforms(&[(child, child_global), (parent, parent_global)]);
+        graph.update_hierarchical_data();
+
+        assert!((graph.global_transform(parent) - parent_global).norm() < 1.0e-4);
+        assert!((graph.global_transform(child) - child_global).norm() < 1.0e-4);
+    }
+
+    #[test]
+    fn graph_load_with_invalid_root_test() {
+        let mut graph = Graph::new();
+        let a = BaseBuilder::new().build(&mut graph);
+        let b = BaseBuilder::new().build(&mut graph);
+        graph.link_nodes(b, a);
+        let a_id = graph[a].id();
+        let b_id = graph[b].id();
+        // Simulate malformed file: stored root handle points to a vacant entry.
+        graph.pool.free(graph.root);
+
+        let path = std::env::temp_dir().join("rg3d_graph2d_invalid_root_test.bin");
+        let mut visitor = Visitor::new();
+        graph.visit("Graph", &mut visitor).unwrap();
+        visitor.save_binary(&path).unwrap();
+
+        let mut visitor = block_on(Visitor::load_binary(&path)).unwrap();
+        let mut loaded = Graph::default();
+        loaded.visit("Graph", &mut visitor).unwrap();
+
+        let root = loaded.get_root();
+        assert!(loaded.is_valid_handle(root));
+        assert!(loaded.validate().is_ok());
+        let a = loaded.node_by_id(a_id);
+        let b = loaded.node_by_id(b_id);
+        assert_eq!(loaded[a].parent(), root);
+        assert_eq!(loaded[b].parent(), a);
+        assert_eq!(loaded[a].children(), &[b]);
+    }
+
+    #[test]
+    fn graph_detach_children_test() {
+        let mut graph = Graph::new();
+        let parent = BaseBuilder::new().build(&mut graph);
+        let a = BaseBuilder::new().build(&mut graph);
+        let b = BaseBuilder::new().build(&mut graph);
+        graph.link_nodes(a, parent);
+        graph.link_nodes(b, parent);
+
+        assert_eq!(graph.detach_children(parent), vec![a, b]);
+        assert!(graph[parent].children().is_empty());
+        assert_eq!(graph[a].parent(), graph.get_root());
+
+        let root_children = graph[graph.get_root()].children().to_vec();
+        assert!(graph.detach_children(graph.get_root()).is_empty());
+        assert_eq!(graph[graph.get_root()].children(), root_children.as_slice());
     }
 }
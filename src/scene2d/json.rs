@@ -0,0 +1,247 @@
+//! Human-readable JSON representation of a 2D graph, see [`Graph::to_json`] and
+//! [`Graph::from_json`]. Available only with `serde_integration` feature.
+//!
+//! Only hierarchy, kinds, names, local transforms and visibility of nodes are stored, nodes
+//! are referenced by their stable ids. Kind-specific data (sprite textures, light colors,
+//! etc.) is not stored, nodes are restored with default values of their kind.
+
+use crate::{
+    core::{pool::Handle, uuid::Uuid},
+    scene2d::{
+        camera::Camera,
+        graph::Graph,
+        light::Light,
+        node::{Node, NodeKind},
+        sprite::Sprite,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// A reason why a graph can not be restored from JSON, see [`Graph::from_json`].
+#[derive(Debug)]
+pub enum JsonError {
+    /// Input is not a valid JSON or it does not match expected structure.
+    Serde(serde_json::Error),
+    /// Id of a node can not be parsed.
+    InvalidId(String),
+    /// Two nodes have the same id.
+    DuplicateId(Uuid),
+}
+
+impl From<serde_json::Error> for JsonError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Serde(e)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonNode {
+    id: String,
+    kind: NodeKind,
+    name: String,
+    position: [f32; 2],
+    rotation: f32,
+    scale: [f32; 2],
+    // Could be missing in files written by older versions.
+    #[serde(default)]
+    pivot: [f32; 2],
+    /// Minimal and maximal rotation angles.
+    #[serde(default)]
+    rotation_limits: Option<[f32; 2]>,
+    visible: bool,
+    children: Vec<JsonNode>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonGraph {
+    active_camera: Option<String>,
+    root: JsonNode,
+}
+
+fn parse_id(id: &str) -> Result<Uuid, JsonError> {
+    Uuid::parse_str(id).map_err(|_| JsonError::InvalidId(id.to_owned()))
+}
+
+impl Graph {
+    fn node_to_json(&self, handle: Handle<Node>) -> JsonNode {
+        let node = &self[handle];
+        let transform = node.local_transform();
+        JsonNode {
+            id: node.id().to_string(),
+            kind: node.kind(),
+            name: node.name().to_owned(),
+            position: transform.position().into(),
+            rotation: transform.rotation(),
+            scale: transform.scale().into(),
+            pivot: transform.pivot().into(),
+            rotation_limits: transform.rotation_limits().map(|(min, max)| [min, max]),
+            visible: node.visibility(),
+            children: node
+                .children()
+                .iter()
+                .map(|&child| self.node_to_json(child))
+                .collect(),
+        }
+    }
+
+    /// Writes hierarchy, names, local transforms and visibility of every node to a
+    /// pretty-printed JSON string, see [module docs](crate::scene2d::json) for more info.
+    pub fn to_json(&self) -> String {
+        let camera = self.active_camera();
+        let graph = JsonGraph {
+            active_camera: if self.is_valid_handle(camera) {
+                Some(self[camera].id().to_string())
+            } else {
+                None
+            },
+            root: self.node_to_json(self.get_root()),
+        };
+        // Serialization of plain structs with string keys can not fail.
+        serde_json::to_string_pretty(&graph).unwrap()
+    }
+
+    /// Restores a graph from JSON string produced by [`Self::to_json`] (or by external tools
+    /// that follow the same structure). Stable ids of nodes are preserved.
+    pub fn from_json(json: &str) -> Result<Graph, JsonError> {
+        let desc: JsonGraph = serde_json::from_str(json)?;
+
+        let mut graph = Graph::new();
+        let mut ids = HashSet::new();
+        let root = graph.get_root();
+        apply_common(&mut graph[root], &desc.root, &mut ids)?;
+
+        // Children are pushed in reverse order to keep their order after linking.
+        let mut stack = desc
+            .root
+            .children
+            .iter()
+            .rev()
+            .map(|child| (child, root))
+            .collect::<Vec<_>>();
+        while let Some((node_desc, parent)) = stack.pop() {
+            let mut node = match node_desc.kind {
+                NodeKind::Base => Node::Base(Default::default()),
+                NodeKind::Camera => Node::Camera(Camera::default()),
+                NodeKind::Light => Node::Light(Light::default()),
+                NodeKind::Sprite => Node::Sprite(Sprite::default()),
+            };
+            apply_common(&mut node, node_desc, &mut ids)?;
+            let handle = graph.add_node(node);
+            graph.link_nodes(handle, parent);
+            stack.extend(node_desc.children.iter().rev().map(|child| (child, handle)));
+        }
+        graph.rebuild_id_map();
+
+        if let Some(camera) = desc.active_camera {
+            let camera = graph.node_by_id(parse_id(&camera)?);
+            graph.set_active_camera(camera);
+        }
+
+        Ok(graph)
+    }
+}
+
+fn apply_common(
+    node: &mut Node,
+    desc: &JsonNode,
+    ids: &mut HashSet<Uuid>,
+) -> Result<(), JsonError> {
+    let id = parse_id(&desc.id)?;
+    if !ids.insert(id) {
+        return Err(JsonError::DuplicateId(id));
+    }
+    node.id = id;
+    node.set_name(&desc.name);
+    node.set_visibility(desc.visible);
+    node.local_transform_mut()
+        .set_position(desc.position.into())
+        .set_rotation_limits(desc.rotation_limits.map(|[min, max]| (min, max)))
+        .set_rotation(desc.rotation)
+        .set_scale(desc.scale.into())
+        .set_pivot(desc.pivot.into());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::algebra::Vector2,
+        scene2d::{
+            base::BaseBuilder, camera::CameraBuilder, graph::Graph, sprite::SpriteBuilder,
+            transform::TransformBuilder,
+        },
+    };
+
+    #[test]
+    fn json_round_trip_test() {
+        let mut graph = Graph::new();
+        let parent = BaseBuilder::new()
+            .with_name("parent".to_owned())
+            .with_local_transform(
+                TransformBuilder::new()
+                    .with_position(Vector2::new(1.0, 2.0))
+                    .with_rotation(0.5)
+                    .with_scale(Vector2::new(3.0, 4.0))
+                    .with_pivot(Vector2::new(5.0, 6.0))
+                    .with_rotation_limits(-1.0, 1.0)
+                    .build(),
+            )
+            .build(&mut graph);
+        let first =
+            SpriteBuilder::new(BaseBuilder::new().with_name("first".to_owned())).build(&mut graph);
+        let second = BaseBuilder::new()
+            .with_name("second".to_owned())
+            .with_visibility(false)
+            .build(&mut graph);
+        graph.link_nodes(first, parent);
+        graph.link_nodes(second, parent);
+        let camera = CameraBuilder::new(BaseBuilder::new()).build(&mut graph);
+        graph.set_active_camera(camera);
+
+        let loaded = Graph::from_json(&graph.to_json()).unwrap();
+        assert!(loaded.validate().is_ok());
+        assert_eq!(loaded.node_count(), graph.node_count());
+        assert_eq!(loaded[loaded.active_camera()].id(), graph[camera].id());
+
+        let loaded_parent = loaded.node_by_id(graph[parent].id());
+        assert_eq!(loaded[loaded_parent].name(), "parent");
+        assert_eq!(
+            loaded[loaded_parent].local_transform(),
+            graph[parent].local_transform()
+        );
+        let children = loaded[loaded_parent]
+            .children()
+            .iter()
+            .map(|&child| loaded[child].id())
+            .collect::<Vec<_>>();
+        assert_eq!(children, vec![graph[first].id(), graph[second].id()]);
+        assert!(loaded[loaded.node_by_id(graph[first].id())].is_sprite());
+        assert!(!loaded[loaded.node_by_id(graph[second].id())].visibility());
+
+        assert!(Graph::from_json("{").is_err());
+    }
+
+    #[test]
+    fn json_without_new_fields_test() {
+        // Written before pivots and rotation limits were stored.
+        let json = r#"{
+            "active_camera": null,
+            "root": {
+                "id": "6d8b746c-1dc4-4a4d-9d2d-7f6f2c6a0b1e",
+                "kind": "Base",
+                "name": "root",
+                "position": [1.0, 2.0],
+                "rotation": 0.0,
+                "scale": [1.0, 1.0],
+                "visible": true,
+                "children": []
+            }
+        }"#;
+        let graph = Graph::from_json(json).unwrap();
+        let transform = graph[graph.get_root()].local_transform();
+        assert_eq!(transform.position(), Vector2::new(1.0, 2.0));
+        assert_eq!(transform.pivot(), Vector2::default());
+        assert_eq!(transform.rotation_limits(), None);
+    }
+}
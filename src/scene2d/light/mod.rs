@@ -36,6 +36,17 @@ impl DerefMut for Light {
     }
 }
 
+impl Light {
+    /// Shallow copy of node data. You should never use this directly, shallow copy
+    /// will produce invalid node in most cases!
+    pub fn raw_copy(&self) -> Self {
+        match self {
+            Light::Point(v) => Light::Point(v.raw_copy()),
+            Light::Spot(v) => Light::Spot(v.raw_copy()),
+        }
+    }
+}
+
 impl Default for Light {
     fn default() -> Self {
         Self::Spot(Default::default())
@@ -63,6 +74,15 @@ impl DerefMut for BaseLight {
 }
 
 impl BaseLight {
+    /// Shallow copy of node data. You should never use this directly, shallow copy
+    /// will produce invalid node in most cases!
+    pub fn raw_copy(&self) -> Self {
+        Self {
+            base: self.base.raw_copy(),
+            color: self.color,
+        }
+    }
+
     pub fn color(&self) -> Color {
         self.color
     }
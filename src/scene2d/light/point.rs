@@ -15,6 +15,15 @@ pub struct PointLight {
 }
 
 impl PointLight {
+    /// Shallow copy of node data. You should never use this directly, shallow copy
+    /// will produce invalid node in most cases!
+    pub fn raw_copy(&self) -> Self {
+        Self {
+            base_light: self.base_light.raw_copy(),
+            radius: self.radius,
+        }
+    }
+
     pub fn radius(&self) -> f32 {
         self.radius
     }
@@ -17,6 +17,17 @@ pub struct SpotLight {
 }
 
 impl SpotLight {
+    /// Shallow copy of node data. You should never use this directly, shallow copy
+    /// will produce invalid node in most cases!
+    pub fn raw_copy(&self) -> Self {
+        Self {
+            base_light: self.base_light.raw_copy(),
+            radius: self.radius,
+            hotspot_angle: self.hotspot_angle,
+            falloff_angle_delta: self.falloff_angle_delta,
+        }
+    }
+
     pub fn radius(&self) -> f32 {
         self.radius
     }
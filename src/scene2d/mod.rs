@@ -3,6 +3,7 @@ use crate::{
         algebra::{Isometry2, Translation2, Vector2},
         color::Color,
         instant,
+        math::Rect,
         pool::{Handle, Pool},
         visitor::prelude::*,
     },
@@ -19,13 +20,57 @@ use std::{
 
 pub mod base;
 pub mod camera;
+pub mod cursor;
+pub mod diff;
 pub mod graph;
+#[cfg(feature = "serde_integration")]
+pub mod json;
 pub mod light;
 pub mod node;
 pub mod physics;
 pub mod sprite;
 pub mod transform;
 
+/// Calculates axis-aligned rectangle that encloses all given points. Returns empty rectangle
+/// at origin if there are no points.
+pub(in crate) fn bounding_rect(points: &[Vector2<f32>]) -> Rect<f32> {
+    let mut iter = points.iter();
+    let first = match iter.next() {
+        Some(first) => *first,
+        None => return Rect::default(),
+    };
+    let (min, max) = iter.fold((first, first), |(min, max), p| {
+        (
+            Vector2::new(min.x.min(p.x), min.y.min(p.y)),
+            Vector2::new(max.x.max(p.x), max.y.max(p.y)),
+        )
+    });
+    Rect::new(min.x, min.y, max.x - min.x, max.y - min.y)
+}
+
+/// Calculates intersection of two rectangles. Returns empty rectangle if the rectangles do not
+/// intersect.
+pub(in crate) fn intersect_rects(a: Rect<f32>, b: Rect<f32>) -> Rect<f32> {
+    let left = a.x().max(b.x());
+    let top = a.y().max(b.y());
+    let right = (a.x() + a.w()).min(b.x() + b.w());
+    let bottom = (a.y() + a.h()).min(b.y() + b.h());
+    Rect::new(left, top, (right - left).max(0.0), (bottom - top).max(0.0))
+}
+
+/// Helper to load old versions: visits a field that could be missing in files saved before
+/// the field was added. Missing field keeps its current (usually default) value on read.
+pub(in crate) fn visit_optional<T: Visit>(
+    value: &mut T,
+    name: &str,
+    visitor: &mut Visitor,
+) -> VisitResult {
+    match value.visit(name, visitor) {
+        Err(_) if visitor.is_reading() => Ok(()),
+        result => result,
+    }
+}
+
 /// A structure that holds times that specific update step took.
 #[derive(Clone, Default, Debug)]
 pub struct PerformanceStatistics {
@@ -112,6 +157,8 @@ impl Scene2d {
         }
     }
 
+    /// Updates physics and the graph of the scene. `dt` is time (in seconds) passed since
+    /// previous update.
     pub fn update(&mut self, render_target_size: Vector2<f32>, dt: f32) {
         self.update_physics();
 
@@ -1,8 +1,29 @@
 use crate::{
-    core::{define_is_as, visitor::prelude::*},
-    scene2d::{base::Base, camera::Camera, light::Light, sprite::Sprite},
+    core::{define_is_as, math::Rect, pool::Handle, visitor::prelude::*},
+    scene2d::{
+        base::{Base, PrefabProperty},
+        camera::Camera,
+        graph::RemapHandles,
+        light::Light,
+        sprite::Sprite,
+    },
 };
-use std::ops::{Deref, DerefMut};
+#[cfg(feature = "serde_integration")]
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+};
+
+/// Kind of a node without its data, see [`Node::kind`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde_integration", derive(Serialize, Deserialize))]
+pub enum NodeKind {
+    Base,
+    Camera,
+    Light,
+    Sprite,
+}
 
 #[derive(Visit)]
 pub enum Node {
@@ -44,7 +65,93 @@ impl Default for Node {
 }
 
 impl Node {
+    /// Shallow copy of node data. You should never use this directly, shallow copy
+    /// will produce invalid node in most cases!
+    pub fn raw_copy(&self) -> Self {
+        match self {
+            Node::Base(v) => Node::Base(v.raw_copy()),
+            Node::Camera(v) => Node::Camera(v.raw_copy()),
+            Node::Light(v) => Node::Light(v.raw_copy()),
+            Node::Sprite(v) => Node::Sprite(v.raw_copy()),
+        }
+    }
+
+    /// Returns kind of the node.
+    pub fn kind(&self) -> NodeKind {
+        match self {
+            Node::Base(_) => NodeKind::Base,
+            Node::Camera(_) => NodeKind::Camera,
+            Node::Light(_) => NodeKind::Light,
+            Node::Sprite(_) => NodeKind::Sprite,
+        }
+    }
+
     define_is_as!(Node : Camera -> ref Camera => fn is_camera, fn as_camera, fn as_camera_mut);
     define_is_as!(Node : Light -> ref Light => fn is_light, fn as_light, fn as_light_mut);
     define_is_as!(Node : Sprite -> ref Sprite => fn is_sprite, fn as_sprite, fn as_sprite_mut);
+
+    /// Returns bounds of the node in its local coordinate system, or `None` if the node has
+    /// no size (e.g. pivots, cameras, lights). [Bounds override](Base::bounds_override) takes
+    /// precedence over intrinsic size of the node.
+    pub fn local_bounds(&self) -> Option<Rect<f32>> {
+        if let Some(bounds) = self.bounds_override() {
+            return Some(bounds);
+        }
+        match self {
+            Node::Sprite(sprite) => Some(sprite.local_bounds()),
+            _ => None,
+        }
+    }
+
+    /// Returns handles of other nodes this node refers to (rotation constraint target, etc.),
+    /// hierarchy links (parent and children) are not included. Handles are returned as is,
+    /// they could be invalid.
+    pub fn referenced_handles(&self) -> Vec<Handle<Node>> {
+        // None of node kinds stores handles of its own, only base data does.
+        self.rotation_constraint
+            .iter()
+            .map(|constraint| constraint.target)
+            .collect()
+    }
+
+    /// Fixes handles of other nodes this node refers to (see [`Self::referenced_handles`])
+    /// using given old-to-new map. Just like [`RemapHandles`] does, handles that are not in the
+    /// map become [`Handle::NONE`]; a rotation constraint without target is removed.
+    pub fn remap_handles(&mut self, map: &HashMap<Handle<Node>, Handle<Node>>) {
+        if let Some(constraint) = self.rotation_constraint.as_mut() {
+            constraint.target.remap(map);
+            if constraint.target.is_none() {
+                self.rotation_constraint = None;
+            }
+        }
+    }
+
+    /// Copies handles of other nodes (see [`Self::referenced_handles`]) from other node, which
+    /// is usually the node this one is a [shallow copy](Self::raw_copy) of. Copied handles are
+    /// most likely invalid for the copy and must be fixed by [`Self::remap_handles`].
+    pub(in crate) fn copy_references(&mut self, other: &Node) {
+        self.rotation_constraint = other.rotation_constraint;
+    }
+
+    /// Sets a property of the node, see [`PrefabProperty`].
+    pub(in crate) fn apply_prefab_property(&mut self, property: &PrefabProperty) {
+        match property {
+            PrefabProperty::Name(name) => self.set_name(name),
+            PrefabProperty::Visibility(visibility) => self.set_visibility(*visibility),
+            PrefabProperty::Position(position) => {
+                self.local_transform_mut().set_position(*position);
+            }
+            PrefabProperty::Rotation(rotation) => {
+                self.local_transform_mut().set_rotation(*rotation);
+            }
+            PrefabProperty::Scale(scale) => {
+                self.local_transform_mut().set_scale(*scale);
+            }
+            PrefabProperty::Color(color) => match self {
+                Node::Sprite(sprite) => sprite.set_color(*color),
+                Node::Light(light) => light.set_color(*color),
+                Node::Base(_) | Node::Camera(_) => {}
+            },
+        }
+    }
 }
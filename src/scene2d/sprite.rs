@@ -9,6 +9,7 @@ use crate::{
     resource::texture::Texture,
     scene2d::{
         base::{Base, BaseBuilder},
+        bounding_rect,
         graph::Graph,
         node::Node,
     },
@@ -49,6 +50,17 @@ impl DerefMut for Sprite {
 }
 
 impl Sprite {
+    /// Shallow copy of node data. You should never use this directly, shallow copy
+    /// will produce invalid node in most cases!
+    pub fn raw_copy(&self) -> Self {
+        Self {
+            base: self.base.raw_copy(),
+            texture: self.texture.clone(),
+            color: self.color,
+            size: self.size,
+        }
+    }
+
     pub fn texture(&self) -> Option<&Texture> {
         self.texture.as_ref()
     }
@@ -73,32 +85,29 @@ impl Sprite {
         self.size = size;
     }
 
+    /// Returns bounds of the sprite in its local coordinate system.
     pub fn local_bounds(&self) -> Rect<f32> {
-        Rect {
-            position: self.local_transform().position(),
-            size: Vector2::new(self.size, self.size),
-        }
+        Rect::new(0.0, 0.0, self.size, self.size)
     }
 
+    /// Returns axis-aligned bounds of the sprite in world coordinates. All four corners
+    /// are transformed, so the bounds are correct for rotated sprites too.
     pub fn global_bounds(&self) -> Rect<f32> {
-        let mut bounds = Rect::default();
-        let local_top_left = self.local_transform().position();
-        let local_bottom_right = local_top_left + Vector2::new(self.size, self.size);
-        let global_top_left = self
-            .global_transform()
-            .transform_point(&Point3::new(local_top_left.x, local_top_left.y, 0.0))
-            .coords;
-        let global_bottom_right = self
-            .global_transform()
-            .transform_point(&Point3::new(
-                local_bottom_right.x,
-                local_bottom_right.y,
-                0.0,
-            ))
-            .coords;
-        bounds.push(global_top_left.xy());
-        bounds.push(global_bottom_right.xy());
-        bounds
+        let local_bounds = self.local_bounds();
+        let global_transform = self.global_transform();
+        let transform = |p: Vector2<f32>| {
+            global_transform
+                .transform_point(&Point3::new(p.x, p.y, 0.0))
+                .coords
+                .xy()
+        };
+
+        bounding_rect(&[
+            transform(local_bounds.left_top_corner()),
+            transform(local_bounds.right_top_corner()),
+            transform(local_bounds.right_bottom_corner()),
+            transform(local_bounds.left_bottom_corner()),
+        ])
     }
 }
 
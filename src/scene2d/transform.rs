@@ -1,13 +1,35 @@
-use crate::core::{
-    algebra::{Matrix3, Matrix4, Vector2, Vector3},
-    visitor::prelude::*,
+use crate::{
+    core::{
+        algebra::{Matrix3, Matrix4, UnitQuaternion, Vector2, Vector3},
+        visitor::prelude::*,
+    },
+    scene,
+    scene2d::visit_optional,
 };
 
-#[derive(Visit, Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Transform {
     position: Vector2<f32>,
     scale: Vector2<f32>,
     rotation: f32,
+    pivot: Vector2<f32>,
+    /// Minimal (x) and maximal (y) rotation angles.
+    rotation_limits: Option<Vector2<f32>>,
+}
+
+impl Visit for Transform {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.position.visit("Position", visitor)?;
+        self.scale.visit("Scale", visitor)?;
+        self.rotation.visit("Rotation", visitor)?;
+        // Could be missing in files saved by older versions.
+        visit_optional(&mut self.pivot, "Pivot", visitor)?;
+        visit_optional(&mut self.rotation_limits, "RotationLimits", visitor)?;
+
+        visitor.leave_region()
+    }
 }
 
 impl Default for Transform {
@@ -16,6 +38,8 @@ impl Default for Transform {
             position: Vector2::new(0.0, 0.0),
             scale: Vector2::new(1.0, 1.0),
             rotation: 0.0,
+            pivot: Vector2::new(0.0, 0.0),
+            rotation_limits: None,
         }
     }
 }
@@ -30,8 +54,10 @@ impl Transform {
         self.position
     }
 
+    /// Sets rotation angle (in radians), the angle is clamped to
+    /// [rotation limits](Self::set_rotation_limits) if there are any.
     pub fn set_rotation(&mut self, rotation: f32) -> &mut Self {
-        self.rotation = rotation;
+        self.rotation = self.clamp_rotation(rotation);
         self
     }
 
@@ -48,20 +74,60 @@ impl Transform {
         self.scale
     }
 
+    /// Sets a point (in local coordinates) around which rotation and scale are applied, the
+    /// point stays at `position + pivot` no matter of rotation and scale.
+    pub fn set_pivot(&mut self, pivot: Vector2<f32>) -> &mut Self {
+        self.pivot = pivot;
+        self
+    }
+
+    pub fn pivot(&self) -> Vector2<f32> {
+        self.pivot
+    }
+
     pub fn offset(&mut self, offset: Vector2<f32>) -> &mut Self {
         self.position += offset;
         self
     }
 
+    /// Adds given angle to rotation, result is clamped to
+    /// [rotation limits](Self::set_rotation_limits) if there are any.
     pub fn turn(&mut self, delta: f32) -> &mut Self {
-        self.rotation += delta;
+        self.rotation = self.clamp_rotation(self.rotation + delta);
         self
     }
 
+    /// Sets minimal and maximal rotation angles (in radians), a door or an arm can then swing
+    /// only within its allowed arc. Rotations outside of the limits are clamped rather than
+    /// rejected, current rotation is clamped right away. Limits given in wrong order are
+    /// swapped. `None` removes the limits.
+    pub fn set_rotation_limits(&mut self, limits: Option<(f32, f32)>) -> &mut Self {
+        self.rotation_limits = limits.map(|(min, max)| Vector2::new(min.min(max), max.max(min)));
+        self.rotation = self.clamp_rotation(self.rotation);
+        self
+    }
+
+    pub fn rotation_limits(&self) -> Option<(f32, f32)> {
+        self.rotation_limits.map(|limits| (limits.x, limits.y))
+    }
+
+    /// Returns given angle clamped to rotation limits, or the angle itself if there are no
+    /// limits.
+    pub fn clamp_rotation(&self, rotation: f32) -> f32 {
+        match self.rotation_limits {
+            Some(limits) => rotation.max(limits.x).min(limits.y),
+            None => rotation,
+        }
+    }
+
     pub fn matrix(&self) -> Matrix4<f32> {
-        Matrix4::new_translation(&Vector3::new(self.position.x, self.position.y, 0.0))
-            * Matrix3::new_rotation(self.rotation).to_homogeneous()
+        Matrix4::new_translation(&Vector3::new(
+            self.position.x + self.pivot.x,
+            self.position.y + self.pivot.y,
+            0.0,
+        )) * Matrix3::new_rotation(self.rotation).to_homogeneous()
             * Matrix4::new_nonuniform_scaling(&Vector3::new(self.scale.x, self.scale.y, 1.0))
+            * Matrix4::new_translation(&Vector3::new(-self.pivot.x, -self.pivot.y, 0.0))
     }
 }
 
@@ -69,6 +135,8 @@ pub struct TransformBuilder {
     position: Vector2<f32>,
     scale: Vector2<f32>,
     rotation: f32,
+    pivot: Vector2<f32>,
+    rotation_limits: Option<(f32, f32)>,
 }
 
 impl Default for TransformBuilder {
@@ -83,6 +151,8 @@ impl TransformBuilder {
             position: Default::default(),
             scale: Vector2::new(1.0, 1.0),
             rotation: 0.0,
+            pivot: Default::default(),
+            rotation_limits: None,
         }
     }
 
@@ -101,11 +171,87 @@ impl TransformBuilder {
         self
     }
 
+    pub fn with_pivot(mut self, pivot: Vector2<f32>) -> Self {
+        self.pivot = pivot;
+        self
+    }
+
+    pub fn with_rotation_limits(mut self, min: f32, max: f32) -> Self {
+        self.rotation_limits = Some((min, max));
+        self
+    }
+
     pub fn build(self) -> Transform {
-        Transform {
+        let mut transform = Transform {
             position: self.position,
             scale: self.scale,
             rotation: self.rotation,
-        }
+            pivot: self.pivot,
+            rotation_limits: None,
+        };
+        transform.set_rotation_limits(self.rotation_limits);
+        transform
+    }
+}
+
+/// Embeds 2D transform into XY plane of 3D space: position gets z = 0, rotation is done around
+/// Z axis and scale along Z is 1. Pivot becomes both rotation and scaling pivot.
+impl From<&Transform> for scene::transform::Transform {
+    fn from(transform: &Transform) -> Self {
+        scene::transform::TransformBuilder::new()
+            .with_local_position(Vector3::new(
+                transform.position.x,
+                transform.position.y,
+                0.0,
+            ))
+            .with_local_rotation(UnitQuaternion::from_axis_angle(
+                &Vector3::z_axis(),
+                transform.rotation,
+            ))
+            .with_local_scale(Vector3::new(transform.scale.x, transform.scale.y, 1.0))
+            .with_rotation_pivot(Vector3::new(transform.pivot.x, transform.pivot.y, 0.0))
+            .with_scaling_pivot(Vector3::new(transform.pivot.x, transform.pivot.y, 0.0))
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::algebra::{Point3, Vector2},
+        scene2d::transform::TransformBuilder,
+    };
+
+    #[test]
+    fn transform_rotation_limits_test() {
+        let mut transform = TransformBuilder::new()
+            .with_rotation_limits(1.0, -1.0)
+            .build();
+        assert_eq!(transform.rotation_limits(), Some((-1.0, 1.0)));
+        transform.set_rotation(2.0);
+        assert_eq!(transform.rotation(), 1.0);
+        transform.turn(-5.0);
+        assert_eq!(transform.rotation(), -1.0);
+
+        transform.set_rotation_limits(None);
+        transform.set_rotation(2.0);
+        assert_eq!(transform.rotation(), 2.0);
+    }
+
+    #[test]
+    fn transform_pivot_test() {
+        let transform = TransformBuilder::new()
+            .with_position(Vector2::new(1.0, 2.0))
+            .with_rotation(std::f32::consts::FRAC_PI_2)
+            .with_scale(Vector2::new(2.0, 2.0))
+            .with_pivot(Vector2::new(3.0, 4.0))
+            .build();
+        let matrix = transform.matrix();
+
+        // Pivot stays in place, other points rotate and scale around it.
+        let pivot = matrix.transform_point(&Point3::new(3.0, 4.0, 0.0));
+        assert_eq!(pivot, Point3::new(4.0, 6.0, 0.0));
+        let point = matrix.transform_point(&Point3::new(4.0, 4.0, 0.0));
+        assert!((point - Point3::new(4.0, 8.0, 0.0)).norm() < 1.0e-5);
     }
 }